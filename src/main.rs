@@ -90,7 +90,7 @@ fn generate(args: &ArgMatches<'_>) {
         s.parse::<bool>()
             .expect("Error parsing 'overwrite' as boolean value")
     });
-    service.prepare_feature_queries();
+    webserver::prepare_service_queries(&mut service);
     let stats = service.generate(
         tileset, minzoom, maxzoom, extent, nodes, nodeno, progress, overwrite,
     );
@@ -125,7 +125,7 @@ fn drilldown(args: &ArgMatches<'_>) {
         s.parse::<bool>()
             .expect("Error parsing 'progress' as boolean value")
     });
-    service.prepare_feature_queries();
+    webserver::prepare_service_queries(&mut service);
     let stats = service.drilldown(tileset, minzoom, maxzoom, points, progress);
     print!("{}", stats.as_csv());
 }
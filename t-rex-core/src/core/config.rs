@@ -54,16 +54,87 @@ pub struct DatasourceCfg {
     pub name: Option<String>,
     pub default: Option<bool>,
     // Postgis
-    pub dbconn: Option<String>,
+    pub dbconn: Option<DbConnCfg>,
     pub pool: Option<u16>,
+    /// Maximal time in seconds to wait for a connection (default: 30)
+    pub connection_timeout: Option<u64>,
+    /// Number of connection retries at startup (default: 0)
+    pub connect_retries: Option<u32>,
+    /// Interval in seconds for logging connection pool statistics
+    pub pool_stats_interval: Option<u64>,
+    /// TLS mode (disable, prefer, require)
+    pub sslmode: Option<String>,
+    /// Client certificate with private key (PKCS#12 archive)
+    pub sslcert: Option<String>,
+    /// Password of the client certificate archive
+    pub sslcert_password: Option<String>,
+    /// Root certificate for verifying the server (PEM or DER)
+    pub sslrootcert: Option<String>,
+    /// Vector tile encoding (t-rex, postgis)
+    pub mvt_mode: Option<String>,
+    /// Prepare layer queries on all pooled connections at startup
+    pub prewarm: Option<bool>,
+    /// Check connections before handing them out from the pool (default: true)
+    pub test_on_check_out: Option<bool>,
+    /// Query for checking pooled connections (default: empty query)
+    pub validation_query: Option<String>,
+    /// Number of rows fetched per round-trip when reading features (default: 50)
+    pub fetch_size: Option<u32>,
+    /// Fail on columns of unsupported types instead of converting them to TEXT (default: false)
+    pub strict_types: Option<bool>,
+    /// Number of rows sampled for geometry type detection (default: 1000, 0: all rows)
+    pub detect_limit: Option<u32>,
+    /// Compute layer extents with a full table scan instead of using statistics (default: false)
+    pub exact_extent: Option<bool>,
+    /// Schemas included in layer detection (default: all)
+    #[serde(default)]
+    pub include_schemas: Vec<String>,
+    /// Schemas excluded from layer detection
+    #[serde(default)]
+    pub exclude_schemas: Vec<String>,
+    /// Table name pattern for layer detection with `*` and `?` wildcards
+    pub table_filter: Option<String>,
+    /// Names of the layers served from this datasource (default: all layers)
+    #[serde(default)]
+    pub active_layers: Vec<String>,
     // GDAL
     pub path: Option<String>,
+    /// Timeout in seconds for HTTP requests of GDAL drivers like OAPIF or WFS
+    pub http_timeout: Option<u32>,
+}
+
+/// PostgreSQL connection URL or list of URLs (e.g. read replicas)
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum DbConnCfg {
+    Url(String),
+    Urls(Vec<String>),
+}
+
+impl DbConnCfg {
+    pub fn urls(&self) -> Vec<String> {
+        match self {
+            DbConnCfg::Url(url) => vec![url.clone()],
+            DbConnCfg::Urls(urls) => urls.clone(),
+        }
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct GridCfg {
     pub predefined: Option<String>,
+    /// EPSG code of a predefined grid, e.g. 3857
+    pub epsg: Option<i32>,
+    /// Tile size in pixels of a predefined grid, e.g. 512
+    pub tile_size: Option<u16>,
+    /// Restrict served zoom levels without removing resolutions
+    pub minzoom: Option<u8>,
+    pub maxzoom: Option<u8>,
     pub user: Option<UserGridCfg>,
+    /// Screen pixel size in meters for scale denominator calculation (default: 0.00028)
+    pub pixel_size: Option<f64>,
+    /// Adressing scheme of requested tiles (tms or xyz)
+    pub scheme: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -107,6 +178,10 @@ pub struct TilesetCfg {
     // Inline style
     pub style: Option<Value>,
     pub cache_limits: Option<TilesetCacheCfg>,
+    /// Number of tiles in each direction rendered together when seeding
+    pub metatile: Option<u8>,
+    /// Maximum number of features of all layers in a tile
+    pub max_features: Option<u64>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -119,6 +194,18 @@ pub struct LayerQueryCfg {
     /// Simplification tolerance (override layer default setting)
     pub tolerance: Option<String>,
     pub sql: Option<String>,
+    /// Feature filter added to the WHERE clause, e.g. "scalerank <= 4"
+    pub filter: Option<String>,
+    /// Aggregate features into one point per group, e.g. "ST_SnapToGrid(geometry, !pixel_width!*32)"
+    pub group_by: Option<String>,
+    /// Aggregated columns of grouped features (default: "count(*) AS count")
+    pub aggregate: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LayerGeometryCfg {
+    pub geometry_field: String,
+    pub geometry_type: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -127,15 +214,52 @@ pub struct LayerCfg {
     pub datasource: Option<String>,
     pub geometry_field: Option<String>,
     pub geometry_type: Option<String>,
+    /// Coordinate dimensions (3: keep Z ordinate of points. Default: 3 for Z geometry types, otherwise 2)
+    pub dimensions: Option<u8>,
+    /// Additional geometry columns, returned as separate features with the same attributes
+    #[serde(default)]
+    pub additional_geometry: Vec<LayerGeometryCfg>,
     /// Spatial reference system (PostGIS SRID)
     pub srid: Option<i32>,
+    /// SRID of geometries if `srid` is undefined or 0 (default: grid SRID with a warning)
+    pub assume_srid: Option<i32>,
     /// Handle geometry like one in grid SRS
     #[serde(default)]
     pub no_transform: bool,
+    /// SRID of output geometries (default: grid SRID)
+    pub output_srid: Option<i32>,
+    /// Skip check for a GiST index on geometry_field (e.g. for BRIN or partial indexes)
+    #[serde(default)]
+    pub no_index_check: bool,
+    /// Feature id column or SQL expression (e.g. `hashtext(a||b)`). Non-integer ids (e.g. text or uuid) are hashed
     pub fid_field: Option<String>,
+    /// Return only the first row of rows with the same fid_field (SELECT DISTINCT ON)
+    #[serde(default)]
+    pub distinct_on: bool,
+    /// Selected attribute columns (default: all), optionally renamed
+    #[serde(default)]
+    pub fields: Vec<LayerFieldCfg>,
+    /// Attribute columns not selected
+    #[serde(default)]
+    pub exclude_fields: Vec<String>,
     // Input for derived queries
     pub table_name: Option<String>,
     pub query_limit: Option<u32>,
+    /// Handling of tiles exceeding query_limit: truncate (default), error, sample
+    pub on_limit: Option<String>,
+    /// Maximal number of features returned by the database (LIMIT clause).
+    /// Applied after order_by, otherwise the returned features are arbitrary.
+    pub hard_limit: Option<u32>,
+    /// Feature order, e.g. "population DESC", keeping the most important features
+    /// when truncating at query_limit. Sorting requires reading all features of a tile.
+    pub order_by: Option<String>,
+    /// Statement timeout of tile queries in milliseconds (default: no timeout)
+    pub query_timeout: Option<u32>,
+    /// Number of rows fetched per round-trip, overriding the datasource setting
+    pub fetch_size: Option<u32>,
+    /// User defined query parameters, e.g. `{ tenant = "acme" }` for `!tenant!`
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
     // Explicit queries
     #[serde(default)]
     pub query: Vec<LayerQueryCfg>,
@@ -147,15 +271,43 @@ pub struct LayerCfg {
     /// Simplify geometry (lines and polygons)
     #[serde(default)]
     pub simplify: bool,
-    /// Simplification tolerance (default to !pixel_width!/2)
+    /// Simplification tolerance (default to !pixel_width!/2), optionally per zoom level
     #[serde(default = "default_tolerance")]
-    pub tolerance: String,
+    pub tolerance: ToleranceCfg,
+    /// Snap simplified points to a grid of the simplification tolerance: snap, distinct (remove duplicates)
+    pub point_snap: Option<String>,
+    /// Remove repeated points of simplified lines (PostGIS >= 2.2)
+    #[serde(default)]
+    pub remove_repeated_points: bool,
+    /// Distance of repeated points (default: simplification tolerance)
+    pub repeated_points_tolerance: Option<String>,
+    /// Maximal deviation when linearizing curves (default: 32 segments per quarter circle)
+    pub curve_tolerance: Option<String>,
+    /// Minimal area of polygons in units of the layer SRS, e.g. "!pixel_width!*!pixel_width!"
+    pub min_area: Option<String>,
+    /// Minimal length of lines in units of the layer SRS, e.g. "!pixel_width!"
+    pub min_length: Option<String>,
+    /// Merging of geometries grouped with `group_by`: "collect" (default) or "union" (dissolve)
+    pub group_merge: Option<String>,
     /// Tile buffer size in pixels (None: no clipping)
-    pub buffer_size: Option<u32>,
-    /// Fix invalid geometries before clipping (lines and polygons)
+    pub buffer_size: Option<f64>,
+    /// Units of buffer_size: "pixels" (default) or "ground" (units of the grid SRS)
+    pub buffer_units: Option<String>,
+    /// Apply ST_Buffer(geom, 0.0) to clipped polygons (default: true)
+    #[serde(default = "default_clip_buffer_zero")]
+    pub clip_buffer_zero: bool,
+    /// Skip features with empty geometries, e.g. clipped away (default: true)
+    #[serde(default = "default_skip_empty")]
+    pub skip_empty: bool,
+    /// Fix invalid geometries (lines and polygons)
     #[serde(default)]
     pub make_valid: bool,
-    /// Apply ST_Shift_Longitude to (transformed) bbox
+    /// ST_MakeValid parameters, e.g. "method=structure" (PostGIS >= 3.2)
+    pub make_valid_params: Option<String>,
+    /// Convert lines and polygons to multi geometries (default: true)
+    #[serde(default = "default_force_multi")]
+    pub force_multi: bool,
+    /// Apply ST_ShiftLongitude (ST_Shift_Longitude before PostGIS 3) to (transformed) bbox
     #[serde(default)]
     pub shift_longitude: bool,
     // Inline style
@@ -166,10 +318,45 @@ pub fn default_tile_size() -> u32 {
     4096
 }
 
+pub fn default_force_multi() -> bool {
+    true
+}
+
+pub fn default_clip_buffer_zero() -> bool {
+    true
+}
+
+pub fn default_skip_empty() -> bool {
+    true
+}
+
 pub const DEFAULT_TOLERANCE: &str = "!pixel_width!/2";
 
-pub fn default_tolerance() -> String {
-    DEFAULT_TOLERANCE.to_string()
+pub fn default_tolerance() -> ToleranceCfg {
+    ToleranceCfg::Scalar(DEFAULT_TOLERANCE.to_string())
+}
+
+/// Attribute column name or column with optional alias, e.g. `{ from = "pop_2020_est", as = "pop" }`
+///
+/// TOML arrays can't mix strings and tables, so use `{ from = "name" }` together with renamed columns.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum LayerFieldCfg {
+    Name(String),
+    Column {
+        from: String,
+        #[serde(rename = "as")]
+        alias: Option<String>,
+    },
+}
+
+/// Simplification tolerance for all zoom levels or per zoom level
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ToleranceCfg {
+    Scalar(String),
+    /// Tolerance per zoom level, e.g. `{ 4 = "!pixel_width!", 14 = "0.5" }`
+    Zoom(HashMap<String, String>),
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -233,6 +420,20 @@ bind = "127.0.0.1"
 port = 6767
 "#;
 
+/// TOML string with escaped quotes and control characters
+pub(crate) fn toml_string(value: &str) -> String {
+    Value::String(value.to_string()).to_string()
+}
+
+/// TOML array of strings
+pub(crate) fn toml_string_array<S: AsRef<str>>(values: &[S]) -> String {
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| toml_string(value.as_ref()))
+        .collect();
+    format!("[{}]", values.join(", "))
+}
+
 /// Load and parse the config file into an config struct.
 pub fn read_config<'a, T: Deserialize<'a>>(path: &str) -> Result<T, String> {
     let mut file = match File::open(path) {
@@ -69,8 +69,11 @@ fn test_template() {
     assert_eq!(config.as_ref().err(), None);
     let config = config.unwrap();
     assert_eq!(
-        config.datasource[0].dbconn,
-        Some("postgresql://pi@localhost/geostat".to_string())
+        config.datasource[0]
+            .dbconn
+            .as_ref()
+            .map(|dbconn| dbconn.urls()),
+        Some(vec!["postgresql://pi@localhost/geostat".to_string()])
     );
     assert_eq!(&config.tilesets[0].name, "Default-Tileset");
     assert_eq!(config.tilesets[0].layers.len(), 3);
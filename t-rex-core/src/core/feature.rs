@@ -16,6 +16,18 @@ pub enum FeatureAttrValType {
     SInt(i64),
     Bool(bool),
     VarcharArray(Vec<String>),
+    /// Milliseconds since 1970-01-01 00:00:00 UTC
+    Timestamp(i64),
+    /// ISO 8601 date (YYYY-MM-DD)
+    Date(String),
+}
+
+/// Stable (FNV-1a) 64 bit hash of a non-integer feature id.
+/// The hash is not reversible and may collide for different ids.
+pub fn fid_hash(fid: &str) -> u64 {
+    fid.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
 pub trait Feature {
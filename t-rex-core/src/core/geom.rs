@@ -4,9 +4,11 @@
 //
 
 use postgis::ewkb;
+use tile_grid::Extent;
 
 // Aliases for rust-postgis geometry types
 pub type Point = ewkb::Point;
+pub type PointZ = ewkb::PointZ;
 pub type LineString = ewkb::LineString;
 pub type Polygon = ewkb::Polygon;
 pub type MultiPoint = ewkb::MultiPoint;
@@ -18,6 +20,8 @@ pub type GeometryCollection = ewkb::GeometryCollection;
 #[derive(Debug)]
 pub enum GeometryType {
     Point(Point),
+    /// Point with elevation (layers with `dimensions = 3`)
+    PointZ(PointZ),
     LineString(LineString),
     Polygon(Polygon),
     MultiPoint(MultiPoint),
@@ -30,11 +34,26 @@ impl Clone for GeometryType {
     fn clone(&self) -> Self {
         match self {
             &GeometryType::Point(ref p) => GeometryType::Point(Point::new(p.x, p.y, None)),
+            &GeometryType::PointZ(ref p) => GeometryType::PointZ(PointZ::new(p.x, p.y, p.z, None)),
             _ => panic!("Not implemented yet"), // TODO: either implement other types or don't clone (FeatureStruct)...
         }
     }
 }
 
+impl From<ewkb::Geometry> for GeometryType {
+    fn from(geom: ewkb::Geometry) -> Self {
+        match geom {
+            ewkb::GeometryT::Point(g) => GeometryType::Point(g),
+            ewkb::GeometryT::LineString(g) => GeometryType::LineString(g),
+            ewkb::GeometryT::Polygon(g) => GeometryType::Polygon(g),
+            ewkb::GeometryT::MultiPoint(g) => GeometryType::MultiPoint(g),
+            ewkb::GeometryT::MultiLineString(g) => GeometryType::MultiLineString(g),
+            ewkb::GeometryT::MultiPolygon(g) => GeometryType::MultiPolygon(g),
+            ewkb::GeometryT::GeometryCollection(g) => GeometryType::GeometryCollection(g),
+        }
+    }
+}
+
 impl GeometryType {
     pub fn is_empty(&self) -> bool {
         match self {
@@ -46,4 +65,345 @@ impl GeometryType {
             _ => false,
         }
     }
+    /// Bounding box of all coordinates, `None` for empty geometries
+    pub fn bbox(&self) -> Option<Extent> {
+        let mut coords: Vec<(f64, f64)> = Vec::new();
+        match self {
+            &GeometryType::Point(ref p) => coords.push((p.x, p.y)),
+            &GeometryType::PointZ(ref p) => coords.push((p.x, p.y)),
+            &GeometryType::LineString(ref l) => add_line_coords(&mut coords, l),
+            &GeometryType::Polygon(ref p) => add_polygon_coords(&mut coords, p),
+            &GeometryType::MultiPoint(ref mp) => {
+                coords.extend(mp.points.iter().map(|p| (p.x, p.y)))
+            }
+            &GeometryType::MultiLineString(ref ml) => {
+                for l in &ml.lines {
+                    add_line_coords(&mut coords, l);
+                }
+            }
+            &GeometryType::MultiPolygon(ref mp) => {
+                for p in &mp.polygons {
+                    add_polygon_coords(&mut coords, p);
+                }
+            }
+            &GeometryType::GeometryCollection(ref gc) => {
+                for g in &gc.geometries {
+                    if let Some(ext) = GeometryType::from(g.clone()).bbox() {
+                        coords.push((ext.minx, ext.miny));
+                        coords.push((ext.maxx, ext.maxy));
+                    }
+                }
+            }
+        }
+        let (&(x0, y0), rest) = coords.split_first()?;
+        Some(rest.iter().fold(
+            Extent {
+                minx: x0,
+                miny: y0,
+                maxx: x0,
+                maxy: y0,
+            },
+            |ext, &(x, y)| Extent {
+                minx: ext.minx.min(x),
+                miny: ext.miny.min(y),
+                maxx: ext.maxx.max(x),
+                maxy: ext.maxy.max(y),
+            },
+        ))
+    }
+    /// Extract the members of a geometry collection with the given dimension
+    /// (0: points, 1: lines, 2: polygons) into a multi geometry.
+    ///
+    /// Other geometries are returned unchanged. Like `ST_CollectionExtract`,
+    /// an empty multi geometry is returned if no member matches.
+    pub fn collection_extract(self, dim: u8) -> GeometryType {
+        let gc = match self {
+            GeometryType::GeometryCollection(gc) => gc,
+            geom => return geom,
+        };
+        let mut points = Vec::new();
+        let mut lines = Vec::new();
+        let mut polygons = Vec::new();
+        add_collection_members(&mut points, &mut lines, &mut polygons, gc.geometries);
+        match dim {
+            0 => GeometryType::MultiPoint(MultiPoint {
+                points,
+                srid: gc.srid,
+            }),
+            1 => GeometryType::MultiLineString(MultiLineString {
+                lines,
+                srid: gc.srid,
+            }),
+            _ => GeometryType::MultiPolygon(MultiPolygon {
+                polygons,
+                srid: gc.srid,
+            }),
+        }
+    }
+    /// Clip geometry to `extent` like `ST_ClipByBox2D`.
+    ///
+    /// Points outside the extent are removed, lines are split into the parts
+    /// inside and polygon rings are clipped with the Sutherland-Hodgman algorithm.
+    /// Geometry collections are returned unchanged.
+    pub fn clip(self, extent: &Extent) -> GeometryType {
+        match self {
+            GeometryType::Point(p) => {
+                if contains(extent, p.x, p.y) {
+                    GeometryType::Point(p)
+                } else {
+                    GeometryType::MultiPoint(MultiPoint {
+                        points: Vec::new(),
+                        srid: p.srid,
+                    })
+                }
+            }
+            GeometryType::PointZ(p) => {
+                if contains(extent, p.x, p.y) {
+                    GeometryType::PointZ(p)
+                } else {
+                    GeometryType::MultiPoint(MultiPoint {
+                        points: Vec::new(),
+                        srid: p.srid,
+                    })
+                }
+            }
+            GeometryType::MultiPoint(mp) => GeometryType::MultiPoint(MultiPoint {
+                points: mp
+                    .points
+                    .into_iter()
+                    .filter(|p| contains(extent, p.x, p.y))
+                    .collect(),
+                srid: mp.srid,
+            }),
+            GeometryType::LineString(l) => {
+                let mut lines = clip_line(&l, extent);
+                if lines.len() == 1 {
+                    GeometryType::LineString(lines.remove(0))
+                } else {
+                    GeometryType::MultiLineString(MultiLineString {
+                        lines,
+                        srid: l.srid,
+                    })
+                }
+            }
+            GeometryType::MultiLineString(ml) => GeometryType::MultiLineString(MultiLineString {
+                lines: ml.lines.iter().flat_map(|l| clip_line(l, extent)).collect(),
+                srid: ml.srid,
+            }),
+            GeometryType::Polygon(p) => match clip_polygon(&p, extent) {
+                Some(polygon) => GeometryType::Polygon(polygon),
+                None => GeometryType::MultiPolygon(MultiPolygon {
+                    polygons: Vec::new(),
+                    srid: p.srid,
+                }),
+            },
+            GeometryType::MultiPolygon(mp) => GeometryType::MultiPolygon(MultiPolygon {
+                polygons: mp
+                    .polygons
+                    .iter()
+                    .filter_map(|p| clip_polygon(p, extent))
+                    .collect(),
+                srid: mp.srid,
+            }),
+            GeometryType::GeometryCollection(gc) => GeometryType::GeometryCollection(gc),
+        }
+    }
+}
+
+fn contains(extent: &Extent, x: f64, y: f64) -> bool {
+    x >= extent.minx && x <= extent.maxx && y >= extent.miny && y <= extent.maxy
+}
+
+/// Clip segment with the Liang-Barsky algorithm
+fn clip_segment(
+    p0: (f64, f64),
+    p1: (f64, f64),
+    extent: &Extent,
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+    let (mut t0, mut t1) = (0.0, 1.0);
+    for &(p, q) in &[
+        (-dx, p0.0 - extent.minx),
+        (dx, extent.maxx - p0.0),
+        (-dy, p0.1 - extent.miny),
+        (dy, extent.maxy - p0.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = f64::max(t0, r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = f64::min(t1, r);
+            }
+        }
+    }
+    // Keep unclipped end points exact for joining consecutive segments
+    let start = if t0 == 0.0 {
+        p0
+    } else {
+        (p0.0 + t0 * dx, p0.1 + t0 * dy)
+    };
+    let end = if t1 == 1.0 {
+        p1
+    } else {
+        (p0.0 + t1 * dx, p0.1 + t1 * dy)
+    };
+    Some((start, end))
+}
+
+/// Parts of a line inside the extent
+fn clip_line(line: &LineString, extent: &Extent) -> Vec<LineString> {
+    let mut lines = Vec::new();
+    let mut points: Vec<Point> = Vec::new();
+    for segment in line.points.windows(2) {
+        let clipped = clip_segment(
+            (segment[0].x, segment[0].y),
+            (segment[1].x, segment[1].y),
+            extent,
+        );
+        let connected = |x: f64, y: f64| points.last().map_or(false, |p| p.x == x && p.y == y);
+        match clipped {
+            Some((start, end)) if start != end || connected(start.0, start.1) => {
+                if !connected(start.0, start.1) {
+                    if points.len() > 1 {
+                        lines.push(LineString {
+                            points: std::mem::replace(&mut points, Vec::new()),
+                            srid: line.srid,
+                        });
+                    }
+                    points.clear();
+                    points.push(Point::new(start.0, start.1, line.srid));
+                }
+                if start != end {
+                    points.push(Point::new(end.0, end.1, line.srid));
+                }
+            }
+            _ => {
+                if points.len() > 1 {
+                    lines.push(LineString {
+                        points: std::mem::replace(&mut points, Vec::new()),
+                        srid: line.srid,
+                    });
+                }
+                points.clear();
+            }
+        }
+    }
+    if points.len() > 1 {
+        lines.push(LineString {
+            points,
+            srid: line.srid,
+        });
+    }
+    lines
+}
+
+/// Clip ring with the Sutherland-Hodgman algorithm. Returns `None` without remaining area.
+fn clip_ring(ring: &LineString, extent: &Extent) -> Option<LineString> {
+    let mut coords: Vec<(f64, f64)> = ring.points.iter().map(|p| (p.x, p.y)).collect();
+    if coords.len() > 1 && coords.first() == coords.last() {
+        coords.pop();
+    }
+    for edge in 0..4 {
+        let inside = |c: (f64, f64)| match edge {
+            0 => c.0 >= extent.minx,
+            1 => c.0 <= extent.maxx,
+            2 => c.1 >= extent.miny,
+            _ => c.1 <= extent.maxy,
+        };
+        let intersection = |a: (f64, f64), b: (f64, f64)| match edge {
+            0 | 1 => {
+                let x = if edge == 0 { extent.minx } else { extent.maxx };
+                (x, a.1 + (b.1 - a.1) * (x - a.0) / (b.0 - a.0))
+            }
+            _ => {
+                let y = if edge == 2 { extent.miny } else { extent.maxy };
+                (a.0 + (b.0 - a.0) * (y - a.1) / (b.1 - a.1), y)
+            }
+        };
+        let input = std::mem::replace(&mut coords, Vec::new());
+        for (i, &cur) in input.iter().enumerate() {
+            let prev = input[(i + input.len() - 1) % input.len()];
+            match (inside(prev), inside(cur)) {
+                (true, true) => coords.push(cur),
+                (true, false) => coords.push(intersection(prev, cur)),
+                (false, true) => {
+                    coords.push(intersection(prev, cur));
+                    coords.push(cur);
+                }
+                (false, false) => {}
+            }
+        }
+    }
+    // Shoelace formula
+    let area: f64 = (0..coords.len())
+        .map(|i| {
+            let (a, b) = (coords[i], coords[(i + 1) % coords.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    if coords.len() < 3 || area == 0.0 {
+        return None;
+    }
+    coords.push(coords[0]);
+    Some(LineString {
+        points: coords
+            .into_iter()
+            .map(|(x, y)| Point::new(x, y, ring.srid))
+            .collect(),
+        srid: ring.srid,
+    })
+}
+
+/// Polygon clipped to extent, `None` if the exterior ring is outside
+fn clip_polygon(polygon: &Polygon, extent: &Extent) -> Option<Polygon> {
+    let (exterior, interiors) = polygon.rings.split_first()?;
+    let mut rings = vec![clip_ring(exterior, extent)?];
+    rings.extend(interiors.iter().filter_map(|ring| clip_ring(ring, extent)));
+    Some(Polygon {
+        rings,
+        srid: polygon.srid,
+    })
+}
+
+fn add_collection_members(
+    points: &mut Vec<Point>,
+    lines: &mut Vec<LineString>,
+    polygons: &mut Vec<Polygon>,
+    geometries: Vec<ewkb::Geometry>,
+) {
+    for geom in geometries {
+        match geom {
+            ewkb::GeometryT::Point(g) => points.push(g),
+            ewkb::GeometryT::LineString(g) => lines.push(g),
+            ewkb::GeometryT::Polygon(g) => polygons.push(g),
+            ewkb::GeometryT::MultiPoint(g) => points.extend(g.points),
+            ewkb::GeometryT::MultiLineString(g) => lines.extend(g.lines),
+            ewkb::GeometryT::MultiPolygon(g) => polygons.extend(g.polygons),
+            ewkb::GeometryT::GeometryCollection(g) => {
+                add_collection_members(points, lines, polygons, g.geometries)
+            }
+        }
+    }
+}
+
+fn add_line_coords(coords: &mut Vec<(f64, f64)>, line: &LineString) {
+    coords.extend(line.points.iter().map(|p| (p.x, p.y)));
+}
+
+fn add_polygon_coords(coords: &mut Vec<(f64, f64)>, polygon: &Polygon) {
+    // Exterior ring is sufficient
+    if let Some(ring) = polygon.rings.first() {
+        add_line_coords(coords, ring);
+    }
 }
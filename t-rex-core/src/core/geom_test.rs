@@ -24,3 +24,180 @@ fn test_geom_creation() {
     };
     assert_eq!(p.x, 960000.0);
 }
+
+#[test]
+fn test_geom_bbox() {
+    use tile_grid::Extent;
+
+    let p = GeometryType::new_point(960000.0, 6002729.0);
+    assert_eq!(
+        p.bbox(),
+        Some(Extent {
+            minx: 960000.0,
+            miny: 6002729.0,
+            maxx: 960000.0,
+            maxy: 6002729.0,
+        })
+    );
+    let line = ewkb::LineString {
+        points: vec![
+            Point::new(10.0, 20.0, None),
+            Point::new(-5.0, 40.0, None),
+            Point::new(3.0, 30.0, None),
+        ],
+        srid: None,
+    };
+    assert_eq!(
+        GeometryType::LineString(line).bbox(),
+        Some(Extent {
+            minx: -5.0,
+            miny: 20.0,
+            maxx: 10.0,
+            maxy: 40.0,
+        })
+    );
+    let empty = ewkb::MultiPoint {
+        points: vec![],
+        srid: None,
+    };
+    assert_eq!(GeometryType::MultiPoint(empty).bbox(), None);
+}
+
+#[test]
+fn test_collection_extract() {
+    let square = ewkb::Polygon {
+        rings: vec![ewkb::LineString {
+            points: vec![
+                Point::new(0.0, 0.0, None),
+                Point::new(1.0, 0.0, None),
+                Point::new(1.0, 1.0, None),
+                Point::new(0.0, 0.0, None),
+            ],
+            srid: None,
+        }],
+        srid: None,
+    };
+    let line = ewkb::LineString {
+        points: vec![Point::new(0.0, 0.0, None), Point::new(1.0, 0.0, None)],
+        srid: None,
+    };
+    let gc = ewkb::GeometryCollection {
+        geometries: vec![
+            ewkb::GeometryT::Polygon(square),
+            ewkb::GeometryT::LineString(line),
+            ewkb::GeometryT::GeometryCollection(ewkb::GeometryCollection {
+                geometries: vec![ewkb::GeometryT::Point(Point::new(2.0, 2.0, None))],
+                srid: None,
+            }),
+        ],
+        srid: Some(3857),
+    };
+
+    match GeometryType::GeometryCollection(gc).collection_extract(2) {
+        GeometryType::MultiPolygon(mp) => {
+            assert_eq!(mp.polygons.len(), 1);
+            assert_eq!(mp.srid, Some(3857));
+        }
+        _ => panic!("MultiPolygon expected"),
+    }
+
+    let empty = ewkb::GeometryCollection {
+        geometries: vec![],
+        srid: None,
+    };
+    let points = GeometryType::GeometryCollection(empty).collection_extract(0);
+    assert!(points.is_empty());
+
+    let p = GeometryType::new_point(1.0, 2.0).collection_extract(2);
+    assert!(match p {
+        GeometryType::Point(_) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn test_geom_clip() {
+    use tile_grid::Extent;
+
+    let extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: 10.0,
+        maxy: 10.0,
+    };
+    assert!(!GeometryType::new_point(5.0, 10.0).clip(&extent).is_empty());
+    assert!(GeometryType::new_point(5.0, 11.0).clip(&extent).is_empty());
+
+    // Line leaving and re-entering the extent
+    let line = ewkb::LineString {
+        points: vec![
+            Point::new(-5.0, 5.0, None),
+            Point::new(5.0, 5.0, None),
+            Point::new(5.0, 15.0, None),
+            Point::new(8.0, 15.0, None),
+            Point::new(8.0, 5.0, None),
+        ],
+        srid: Some(3857),
+    };
+    match GeometryType::LineString(line).clip(&extent) {
+        GeometryType::MultiLineString(ml) => {
+            assert_eq!(ml.srid, Some(3857));
+            let coords: Vec<Vec<(f64, f64)>> = ml
+                .lines
+                .iter()
+                .map(|l| l.points.iter().map(|p| (p.x, p.y)).collect())
+                .collect();
+            assert_eq!(
+                coords,
+                vec![
+                    vec![(0.0, 5.0), (5.0, 5.0), (5.0, 10.0)],
+                    vec![(8.0, 10.0), (8.0, 5.0)]
+                ]
+            );
+        }
+        _ => panic!("MultiLineString expected"),
+    }
+
+    // Polygon covering the extent with a hole outside
+    let ring = |coords: &[(f64, f64)]| ewkb::LineString {
+        points: coords
+            .iter()
+            .map(|&(x, y)| Point::new(x, y, None))
+            .collect(),
+        srid: None,
+    };
+    let polygon = ewkb::Polygon {
+        rings: vec![
+            ring(&[
+                (-5.0, -5.0),
+                (20.0, -5.0),
+                (20.0, 20.0),
+                (-5.0, 20.0),
+                (-5.0, -5.0),
+            ]),
+            ring(&[(12.0, 12.0), (15.0, 12.0), (15.0, 15.0), (12.0, 12.0)]),
+        ],
+        srid: None,
+    };
+    match GeometryType::Polygon(polygon).clip(&extent) {
+        GeometryType::Polygon(p) => {
+            assert_eq!(p.rings.len(), 1);
+            let bbox = GeometryType::Polygon(p).bbox().unwrap();
+            assert_eq!(format!("{:?}", bbox), format!("{:?}", extent));
+        }
+        _ => panic!("Polygon expected"),
+    }
+
+    // Polygon only touching the extent
+    let polygon = ewkb::Polygon {
+        rings: vec![ring(&[
+            (10.0, 0.0),
+            (20.0, 0.0),
+            (20.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 0.0),
+        ])],
+        srid: None,
+    };
+    assert!(GeometryType::Polygon(polygon).clip(&extent).is_empty());
+}
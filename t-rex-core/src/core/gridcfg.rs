@@ -5,7 +5,7 @@
 
 use crate::core::config::GridCfg;
 use crate::core::Config;
-use tile_grid::{Extent, Grid, Origin, Unit};
+use tile_grid::{Extent, Grid, Origin, TileScheme, Unit};
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct ExtentCfg {
@@ -26,14 +26,65 @@ impl From<&ExtentCfg> for Extent {
     }
 }
 
+/// Resolutions have to be positive and ordered from largest to smallest
+fn check_resolutions(resolutions: &[f64]) -> Result<(), String> {
+    if resolutions.is_empty() {
+        return Err("Grid resolutions must not be empty".to_string());
+    }
+    for (i, res) in resolutions.iter().enumerate() {
+        if !(*res > 0.0) {
+            return Err(format!(
+                "Invalid grid resolution {} at index {}: resolutions must be positive",
+                res, i
+            ));
+        }
+        if i > 0 && *res >= resolutions[i - 1] {
+            return Err(format!(
+                "Invalid grid resolution {} at index {}: resolutions must be strictly decreasing",
+                res, i
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Extent has to cover a positive area
+fn check_extent(extent: &Extent) -> Result<(), String> {
+    let width = extent.maxx - extent.minx;
+    let height = extent.maxy - extent.miny;
+    if !(width > 0.0 && height > 0.0) {
+        return Err(format!(
+            "Invalid grid extent: width {} and height {} must be positive",
+            width, height
+        ));
+    }
+    Ok(())
+}
+
+/// Geographic coordinate systems commonly used with `units = "dd"`
+const GEOGRAPHIC_SRIDS: &[i32] = &[4326, 4258, 4269, 4283, 4674, 4167];
+
+/// Warn about units not matching the spatial reference system
+fn check_units(srid: i32, units: &Unit) {
+    let geographic = GEOGRAPHIC_SRIDS.contains(&srid);
+    match units {
+        Unit::Degrees if !geographic => warn!(
+            "Grid with units 'dd' and SRID {}, which is not a known geographic SRS",
+            srid
+        ),
+        Unit::Meters | Unit::Feet if geographic => {
+            warn!("Grid with projected units and geographic SRID {}", srid)
+        }
+        _ => {}
+    }
+}
+
 impl<'a> Config<'a, GridCfg> for Grid {
     fn from_config(grid_cfg: &GridCfg) -> Result<Self, String> {
-        if let Some(ref gridname) = grid_cfg.predefined {
-            match gridname.as_str() {
-                "wgs84" => Ok(Grid::wgs84()),
-                "web_mercator" => Ok(Grid::web_mercator()),
-                _ => Err(format!("Unkown grid '{}'", gridname)),
-            }
+        let mut grid = if let Some(ref gridname) = grid_cfg.predefined {
+            Grid::predefined(gridname).ok_or(format!("Unkown grid '{}'", gridname))
+        } else if let Some(epsg) = grid_cfg.epsg {
+            Grid::from_epsg(epsg)
         } else if let Some(ref usergrid) = grid_cfg.user {
             let units = match &usergrid.units.to_lowercase() as &str {
                 "m" => Ok(Unit::Meters),
@@ -46,24 +97,60 @@ impl<'a> Config<'a, GridCfg> for Grid {
                 "BottomLeft" => Ok(Origin::BottomLeft),
                 _ => Err(format!("Unexpected enum value '{}'", usergrid.origin)),
             };
+            let units = units?;
+            let extent = Extent::from(&usergrid.extent);
+            check_extent(&extent)?;
+            check_units(usergrid.srid, &units);
+            check_resolutions(&usergrid.resolutions)?;
             let grid = Grid::new(
                 usergrid.width,
                 usergrid.height,
-                Extent::from(&usergrid.extent),
+                extent,
                 usergrid.srid,
-                units?,
+                units,
                 usergrid.resolutions.clone(),
                 origin?,
             );
             Ok(grid)
         } else {
             Err("Invalid grid definition".to_string())
+        }?;
+        if let Some(tile_size) = grid_cfg.tile_size {
+            if tile_size == 0 {
+                return Err(format!("Invalid tile_size {}", tile_size));
+            }
+            grid = grid.with_tile_size(tile_size);
+        }
+        if let Some(pixel_size) = grid_cfg.pixel_size {
+            if pixel_size <= 0.0 {
+                return Err(format!("Invalid pixel_size {}", pixel_size));
+            }
+            grid.pixel_size = pixel_size;
+        }
+        if let Some(ref scheme) = grid_cfg.scheme {
+            grid.scheme = match scheme.to_lowercase().as_str() {
+                "tms" => TileScheme::Tms,
+                "xyz" => TileScheme::Xyz,
+                _ => return Err(format!("Unexpected enum value '{}'", scheme)),
+            };
+        }
+        if grid_cfg.minzoom.is_some() || grid_cfg.maxzoom.is_some() {
+            let maxzoom = grid_cfg.maxzoom.unwrap_or(grid.maxzoom());
+            grid.set_zoom_range(grid_cfg.minzoom.unwrap_or(0), maxzoom)?;
         }
+        Ok(grid)
     }
     fn gen_config() -> String {
         let toml = r#"
 [grid]
 predefined = "web_mercator"
+#epsg = 3857 # Predefined grid by EPSG code (4326, 3857, 2056 or 27700)
+#maxzoom = 14 # Highest served zoom level (default: last resolution)
+#tile_size = 512 # Tile size in pixels covering the same area as 256 pixel tiles
+# Screen pixel size in meters for !scale_denominator! (default: 0.00028, retina: 0.00014)
+#pixel_size = 0.00028
+# Adressing scheme of requested tiles (default: xyz for web_mercator, tms otherwise)
+#scheme = "xyz"
 "#;
         toml.to_string()
     }
@@ -27,6 +27,84 @@ fn test_grid_from_config() {
             maxy: 20037508.3427892480,
         }
     );
+    assert_eq!(grid.scale_denominator(10), 545978.7734655448);
+
+    let toml = r#"
+        #[grid]
+        predefined = "web_mercator"
+        pixel_size = 0.00014
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_eq!(grid.pixel_size, 0.00014);
+    assert_eq!(grid.scale_denominator(10), 1091957.5469310896);
+
+    let toml = r#"
+        #[grid]
+        predefined = "swissgrid_lv95"
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_eq!(grid, Grid::lv95());
+    assert_eq!(grid.srid, 2056);
+
+    let toml = r#"
+        #[grid]
+        predefined = "bng"
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_eq!(grid, Grid::british_national_grid());
+    assert_eq!(grid.srid, 27700);
+
+    let toml = r#"
+        #[grid]
+        predefined = "web_mercator"
+        tile_size = 512
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_eq!(grid, Grid::web_mercator_512());
+    assert_eq!(grid.scale_denominator(10), 272989.3867327724);
+
+    let toml = r#"
+        #[grid]
+        predefined = "web_mercator"
+        maxzoom = 14
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_eq!((grid.minzoom(), grid.maxzoom()), (0, 14));
+    assert_eq!(grid.nlevels(), 23);
+
+    let toml = r#"
+        #[grid]
+        predefined = "bng"
+        minzoom = 14
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    assert_eq!(
+        Grid::from_config(&config).err(),
+        Some("Invalid grid zoom range 14-13 for 14 resolutions".to_string())
+    );
+
+    let toml = r#"
+        #[grid]
+        epsg = 3857
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_eq!(grid, Grid::web_mercator());
+
+    let toml = r#"
+        #[grid]
+        epsg = 3035
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    assert_eq!(
+        Grid::from_config(&config).err(),
+        Some("No predefined grid for EPSG:3035 - use a custom grid definition instead".to_string())
+    );
 
     let toml = r#"
         #[grid.user]
@@ -74,3 +152,126 @@ fn test_grid_from_config() {
         }
     );
 }
+
+#[test]
+fn test_grid_extent() {
+    use crate::core::parse_config;
+
+    let toml = r#"
+        #[grid.user]
+        [user]
+        width = 256
+        height = 256
+        extent = { minx = 2900000.0, miny = 1030000.0, maxx = 2420000.0, maxy = 1350000.0 }
+        srid = 2056
+        units = "m"
+        resolutions = [4000.0,3750.0,3500.0]
+        origin = "TopLeft"
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    assert_eq!(
+        Grid::from_config(&config).err(),
+        Some("Invalid grid extent: width -480000 and height 320000 must be positive".to_string())
+    );
+}
+
+#[test]
+fn test_grid_resolutions() {
+    use crate::core::parse_config;
+
+    let usergrid = |resolutions: &str| {
+        let toml = format!(
+            r#"
+            #[grid.user]
+            [user]
+            width = 256
+            height = 256
+            extent = {{ minx = 2420000.0, miny = 1030000.0, maxx = 2900000.0, maxy = 1350000.0 }}
+            srid = 2056
+            units = "m"
+            resolutions = {}
+            origin = "TopLeft"
+            "#,
+            resolutions
+        );
+        let config: GridCfg = parse_config(toml, "").unwrap();
+        Grid::from_config(&config)
+    };
+    assert!(usergrid("[4000.0,3750.0,3500.0]").is_ok());
+    assert_eq!(
+        usergrid("[4000.0,3500.0,3750.0]").err(),
+        Some(
+            "Invalid grid resolution 3750 at index 2: resolutions must be strictly decreasing"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        usergrid("[4000.0,4000.0]").err(),
+        Some(
+            "Invalid grid resolution 4000 at index 1: resolutions must be strictly decreasing"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        usergrid("[4000.0,0.0]").err(),
+        Some("Invalid grid resolution 0 at index 1: resolutions must be positive".to_string())
+    );
+    assert_eq!(
+        usergrid("[]").err(),
+        Some("Grid resolutions must not be empty".to_string())
+    );
+}
+
+#[test]
+fn test_grid_scheme() {
+    use crate::core::parse_config;
+    use tile_grid::TileScheme;
+
+    let grid_cfg = |toml: &str| {
+        let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+        Grid::from_config(&config)
+    };
+    let grid = grid_cfg(r#"predefined = "web_mercator""#).unwrap();
+    assert_eq!(grid.scheme, TileScheme::Xyz);
+    let grid = grid_cfg(r#"predefined = "wgs84""#).unwrap();
+    assert_eq!(grid.scheme, TileScheme::Tms);
+    let grid = grid_cfg(
+        r#"
+        predefined = "wgs84"
+        scheme = "xyz"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(grid.scheme, TileScheme::Xyz);
+    let grid = grid_cfg(
+        r#"
+        predefined = "web_mercator"
+        scheme = "tms"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(grid.scheme, TileScheme::Tms);
+
+    // User grids are not served in XYZ scheme because of their SRID
+    let usergrid = r#"
+        [user]
+        width = 256
+        height = 256
+        extent = { minx = -20037508.3427892480, miny = -20037508.3427892480, maxx = 20037508.3427892480, maxy = 20037508.3427892480 }
+        srid = 3857
+        units = "m"
+        resolutions = [156543.0339280410, 78271.5169640205]
+        origin = "BottomLeft"
+        "#;
+    assert_eq!(grid_cfg(usergrid).unwrap().scheme, TileScheme::Tms);
+    let grid = grid_cfg(&format!("scheme = \"xyz\"\n{}", usergrid)).unwrap();
+    assert_eq!(grid.scheme, TileScheme::Xyz);
+
+    let grid = grid_cfg(
+        r#"
+        predefined = "wgs84"
+        scheme = "zxy"
+        "#,
+    );
+    assert_eq!(grid.err(), Some("Unexpected enum value 'zxy'".to_string()));
+}
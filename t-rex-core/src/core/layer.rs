@@ -3,10 +3,29 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::core::config::{self, LayerCfg};
+use crate::core::config::{
+    self, toml_string, toml_string_array, LayerCfg, LayerFieldCfg, ToleranceCfg,
+};
 use crate::core::Config;
 use crate::service::glstyle_converter::toml_style_to_gljson;
-use std::collections::HashMap;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use toml::Value;
+
+/// Aggregated columns of grouped features
+pub const DEFAULT_AGGREGATE: &str = "count(*) AS count";
+
+/// Built-in query variables (e.g. `!bbox!`)
+pub const QUERY_VARIABLES: &[&str] = &[
+    "bbox",
+    "zoom",
+    "pixel_width",
+    "scale_denominator",
+    "tile_x",
+    "tile_y",
+    "tile_y_xyz",
+];
 
 #[derive(Clone, Debug)]
 pub struct LayerQuery {
@@ -15,6 +34,233 @@ pub struct LayerQuery {
     pub simplify: Option<bool>,
     pub tolerance: Option<String>,
     pub sql: Option<String>,
+    /// Condition added to the WHERE clause, e.g. `scalerank <= 4`
+    pub filter: Option<String>,
+    /// Aggregate features with the same value of this expression
+    pub group_by: Option<String>,
+    /// Aggregated columns of grouped features (default: `count(*) AS count`)
+    pub aggregate: Option<String>,
+}
+
+/// Additional geometry column of a layer
+#[derive(Clone, Debug)]
+pub struct LayerGeometry {
+    pub geometry_field: String,
+    pub geometry_type: Option<String>,
+}
+
+/// Selected attribute column
+#[derive(PartialEq, Clone, Debug)]
+pub struct LayerField {
+    /// Column name
+    pub name: String,
+    /// Attribute name in tiles
+    pub alias: Option<String>,
+}
+
+impl LayerField {
+    pub fn new(name: &str) -> LayerField {
+        LayerField {
+            name: name.to_string(),
+            alias: None,
+        }
+    }
+    /// Inline table, since arrays of field names can't contain tables
+    fn to_config(&self) -> String {
+        match self.alias {
+            Some(ref alias) => format!("{{ from = \"{}\", as = \"{}\" }}", self.name, alias),
+            None => format!("{{ from = \"{}\" }}", self.name),
+        }
+    }
+}
+
+/// Handling of tiles with more features than `query_limit`
+#[derive(PartialEq, Clone, Debug)]
+pub enum LimitMode {
+    /// Return the first `query_limit` features
+    Truncate,
+    /// Skip the layer
+    Error,
+    /// Return a random sample of `query_limit` features
+    Sample,
+}
+
+impl Default for LimitMode {
+    fn default() -> LimitMode {
+        LimitMode::Truncate
+    }
+}
+
+impl FromStr for LimitMode {
+    type Err = String;
+    fn from_str(on_limit: &str) -> Result<LimitMode, String> {
+        match on_limit {
+            "truncate" => Ok(LimitMode::Truncate),
+            "error" => Ok(LimitMode::Error),
+            "sample" => Ok(LimitMode::Sample),
+            _ => Err(format!("Unexpected enum value '{}'", on_limit)),
+        }
+    }
+}
+
+impl LimitMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            LimitMode::Truncate => "truncate",
+            LimitMode::Error => "error",
+            LimitMode::Sample => "sample",
+        }
+    }
+}
+
+/// Units of the tile buffer size
+#[derive(PartialEq, Clone, Debug)]
+pub enum BufferUnits {
+    /// Pixels of a 256x256 tile
+    Pixels,
+    /// Units of the grid SRS
+    Ground,
+}
+
+impl Default for BufferUnits {
+    fn default() -> BufferUnits {
+        BufferUnits::Pixels
+    }
+}
+
+impl FromStr for BufferUnits {
+    type Err = String;
+    fn from_str(buffer_units: &str) -> Result<BufferUnits, String> {
+        match buffer_units {
+            "pixels" => Ok(BufferUnits::Pixels),
+            "ground" => Ok(BufferUnits::Ground),
+            _ => Err(format!("Unexpected enum value '{}'", buffer_units)),
+        }
+    }
+}
+
+impl BufferUnits {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BufferUnits::Pixels => "pixels",
+            BufferUnits::Ground => "ground",
+        }
+    }
+}
+
+/// Snapping of simplified points to the simplification tolerance grid
+///
+/// Snapped points with identical coordinates are merged with `Distinct`,
+/// which reduces the number of features counted for `query_limit`.
+#[derive(PartialEq, Clone, Debug)]
+pub enum PointSnap {
+    /// Snap coordinates only
+    Snap,
+    /// Snap coordinates and keep one feature per snapped position
+    Distinct,
+}
+
+impl FromStr for PointSnap {
+    type Err = String;
+    fn from_str(point_snap: &str) -> Result<PointSnap, String> {
+        match point_snap {
+            "snap" => Ok(PointSnap::Snap),
+            "distinct" => Ok(PointSnap::Distinct),
+            _ => Err(format!("Unexpected enum value '{}'", point_snap)),
+        }
+    }
+}
+
+impl PointSnap {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PointSnap::Snap => "snap",
+            PointSnap::Distinct => "distinct",
+        }
+    }
+}
+
+/// Merging of grouped geometries (`group_by`)
+#[derive(PartialEq, Clone, Debug)]
+pub enum GroupMerge {
+    /// Collect geometries with `ST_Collect` (fast)
+    Collect,
+    /// Dissolve geometries with `ST_Union`, e.g. boundaries of adjacent polygons (slow)
+    Union,
+}
+
+impl Default for GroupMerge {
+    fn default() -> GroupMerge {
+        GroupMerge::Collect
+    }
+}
+
+impl FromStr for GroupMerge {
+    type Err = String;
+    fn from_str(group_merge: &str) -> Result<GroupMerge, String> {
+        match group_merge {
+            "collect" => Ok(GroupMerge::Collect),
+            "union" => Ok(GroupMerge::Union),
+            _ => Err(format!("Unexpected enum value '{}'", group_merge)),
+        }
+    }
+}
+
+impl GroupMerge {
+    pub fn as_str(&self) -> &str {
+        match self {
+            GroupMerge::Collect => "collect",
+            GroupMerge::Union => "union",
+        }
+    }
+    /// PostGIS aggregate function
+    pub fn sql_function(&self) -> &str {
+        match self {
+            GroupMerge::Collect => "ST_Collect",
+            GroupMerge::Union => "ST_Union",
+        }
+    }
+}
+
+/// Value of a user defined query parameter
+#[derive(PartialEq, Clone, Debug)]
+pub enum ParamValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl ParamValue {
+    fn from_config(name: &str, value: &Value) -> Result<ParamValue, String> {
+        match value {
+            Value::String(v) => Ok(ParamValue::String(v.clone())),
+            Value::Integer(v) => Ok(ParamValue::Int(*v)),
+            Value::Float(v) => Ok(ParamValue::Float(*v)),
+            Value::Boolean(v) => Ok(ParamValue::Bool(*v)),
+            _ => Err(format!(
+                "Unsupported value type of query parameter '{}'",
+                name
+            )),
+        }
+    }
+    /// PostgreSQL type of parameter placeholder
+    pub fn sql_type(&self) -> &str {
+        match self {
+            ParamValue::String(_) => "TEXT",
+            ParamValue::Int(_) => "INT8",
+            ParamValue::Float(_) => "FLOAT8",
+            ParamValue::Bool(_) => "BOOL",
+        }
+    }
+    fn to_config(&self) -> String {
+        match self {
+            ParamValue::String(v) => Value::String(v.clone()).to_string(),
+            ParamValue::Int(v) => v.to_string(),
+            ParamValue::Float(v) => Value::Float(*v).to_string(),
+            ParamValue::Bool(v) => v.to_string(),
+        }
+    }
 }
 
 #[derive(Default, Clone, Debug)]
@@ -23,14 +269,45 @@ pub struct Layer {
     pub datasource: Option<String>,
     pub geometry_field: Option<String>,
     pub geometry_type: Option<String>,
+    /// Coordinate dimensions (3: keep Z ordinate of points)
+    pub dimensions: Option<u8>,
+    /// Additional geometry columns, returned as separate features with the same attributes
+    pub additional_geometry: Vec<LayerGeometry>,
     /// Spatial reference system (PostGIS SRID)
     pub srid: Option<i32>,
+    /// SRID of geometries without SRID, used if `srid` is undefined or 0
+    pub assume_srid: Option<i32>,
     /// Handle geometry like one in grid SRS
     pub no_transform: bool,
+    /// Transform geometries to this SRID instead of the grid SRID.
+    /// The spatial filter still uses the grid SRID.
+    pub output_srid: Option<i32>,
+    /// Skip check for a GiST index on geometry_field
+    pub no_index_check: bool,
+    /// Feature id column or SQL expression. Non-integer ids (e.g. text or uuid) are hashed
     pub fid_field: Option<String>,
+    /// Remove duplicate rows with the same fid_field
+    pub distinct_on: bool,
+    /// Selected attribute columns (empty: all columns)
+    pub fields: Vec<LayerField>,
+    /// Attribute columns not selected
+    pub exclude_fields: Vec<String>,
     // Input for derived queries
     pub table_name: Option<String>,
     pub query_limit: Option<u32>,
+    /// Handling of tiles exceeding query_limit
+    pub on_limit: LimitMode,
+    /// LIMIT clause of tile queries. Unlike `query_limit`, exceeding features are not
+    /// read from the database. Without `order_by`, the returned features are arbitrary.
+    pub hard_limit: Option<u32>,
+    /// ORDER BY expression with column names and sort directions
+    pub order_by: Option<String>,
+    /// Statement timeout of tile queries in milliseconds
+    pub query_timeout: Option<u32>,
+    /// Number of rows fetched per round-trip (default: datasource setting)
+    pub fetch_size: Option<u32>,
+    /// User defined query parameters, bound to `!name!` variables
+    pub params: BTreeMap<String, ParamValue>,
     // Explicit queries
     pub query: Vec<LayerQuery>,
     pub minzoom: Option<u8>,
@@ -41,11 +318,39 @@ pub struct Layer {
     pub simplify: bool,
     /// Simplification tolerance (default to !pixel_width!/2)
     pub tolerance: String,
-    /// Tile buffer size in pixels (None: no clipping)
-    pub buffer_size: Option<u32>,
-    /// Fix invalid geometries before clipping (lines and polygons)
+    /// Simplification tolerance per zoom level (overrides `tolerance`)
+    pub zoom_tolerance: BTreeMap<u8, String>,
+    /// Snap points to simplification tolerance grid
+    pub point_snap: Option<PointSnap>,
+    /// Remove repeated points of simplified lines
+    pub remove_repeated_points: bool,
+    /// Distance of repeated points (default: simplification tolerance)
+    pub repeated_points_tolerance: Option<String>,
+    /// Maximal deviation when linearizing curves (default: 32 segments per quarter circle)
+    pub curve_tolerance: Option<String>,
+    /// Minimal polygon area, SQL expression in units of the layer SRS.
+    /// Checked before clipping, so small slivers at tile edges are kept.
+    pub min_area: Option<String>,
+    /// Minimal line length, SQL expression in units of the layer SRS
+    pub min_length: Option<String>,
+    /// Merging of grouped geometries. Lines and polygons are returned merged,
+    /// points as centroid of the merged geometry.
+    pub group_merge: GroupMerge,
+    /// Tile buffer size (None: no clipping)
+    pub buffer_size: Option<f64>,
+    /// Units of `buffer_size`
+    pub buffer_units: BufferUnits,
+    /// Apply ST_Buffer(geom, 0.0) to clipped polygons
+    pub clip_buffer_zero: bool,
+    /// Skip features with empty geometries, which don't count against feature limits
+    pub skip_empty: bool,
+    /// Fix invalid geometries (lines and polygons)
     pub make_valid: bool,
-    /// Apply ST_Shift_Longitude to (transformed) bbox
+    /// ST_MakeValid parameters, e.g. "method=structure" (PostGIS >= 3.2)
+    pub make_valid_params: Option<String>,
+    /// Convert lines and polygons to multi geometries
+    pub force_multi: bool,
+    /// Apply ST_ShiftLongitude (ST_Shift_Longitude before PostGIS 3) to (transformed) bbox
     pub shift_longitude: bool,
     // Inline style
     pub style: Option<String>,
@@ -56,6 +361,9 @@ impl Layer {
         Layer {
             name: String::from(name),
             tile_size: 4096,
+            force_multi: true,
+            clip_buffer_zero: true,
+            skip_empty: true,
             ..Default::default()
         }
     }
@@ -72,6 +380,46 @@ impl Layer {
                 .unwrap_or(default),
         )
     }
+    /// Configured SRID or `assume_srid` for an unknown SRID
+    pub fn known_srid(&self) -> Option<i32> {
+        match self.srid {
+            Some(srid) if srid > 0 => Some(srid),
+            _ => self.assume_srid,
+        }
+    }
+    /// SRID is taken from `assume_srid`, i.e. geometries need an SRID assigned
+    pub fn srid_assumed(&self) -> bool {
+        self.known_srid().is_some() && self.known_srid() != self.srid
+    }
+    /// Geometry type without Z/M suffix (e.g. POINT for POINTZ)
+    pub fn base_geometry_type(&self) -> Option<&str> {
+        self.geometry_type.as_ref().map(|t| base_geometry_type(t))
+    }
+    /// Geometry type has a Z or M suffix
+    pub fn has_zm_suffix(&self) -> bool {
+        self.geometry_type
+            .as_ref()
+            .map(|t| has_zm_suffix(t))
+            .unwrap_or(false)
+    }
+    /// Coordinate dimensions (default: 3 for Z geometry types, otherwise 2)
+    pub fn dimensions(&self) -> u8 {
+        self.geometry_dimensions(self.geometry_type.as_ref())
+    }
+    /// Coordinate dimensions of a geometry column of this layer
+    pub fn geometry_dimensions(&self, geometry_type: Option<&String>) -> u8 {
+        self.dimensions.unwrap_or_else(|| match geometry_type {
+            Some(ref t) if t.trim_end_matches('M').ends_with('Z') => 3,
+            _ => 2,
+        })
+    }
+    /// Names of all geometry columns (primary geometry first)
+    pub fn geometry_fields(&self) -> Vec<&String> {
+        self.geometry_field
+            .iter()
+            .chain(self.additional_geometry.iter().map(|g| &g.geometry_field))
+            .collect()
+    }
     /// Query config for zoom level
     fn query_cfg<F>(&self, level: u8, check: F) -> Option<&LayerQuery>
     where
@@ -95,6 +443,32 @@ impl Layer {
         let query_cfg = self.query_cfg(level, |q| q.sql.is_some());
         query_cfg.and_then(|q| q.sql.as_ref().and_then(|sql| Some(sql)))
     }
+    /// Feature filter for zoom level
+    pub fn filter(&self, level: u8) -> Option<&String> {
+        let query_cfg = self.query_cfg(level, |q| q.filter.is_some());
+        query_cfg.and_then(|q| q.filter.as_ref())
+    }
+    /// Tile buffer in grid units for a given pixel width
+    pub fn buffer_width(&self, pixel_width: f64) -> f64 {
+        let buffer_size = self.buffer_size.unwrap_or(0.0);
+        match self.buffer_units {
+            BufferUnits::Pixels => buffer_size * pixel_width,
+            BufferUnits::Ground => buffer_size,
+        }
+    }
+    /// Grouping expression and aggregated columns for zoom level
+    pub fn group_by(&self, level: u8) -> Option<(&String, &str)> {
+        let query_cfg = self.query_cfg(level, |q| q.group_by.is_some());
+        query_cfg.and_then(|q| {
+            q.group_by.as_ref().map(|group_by| {
+                let aggregate = q
+                    .aggregate
+                    .as_ref()
+                    .map_or(DEFAULT_AGGREGATE, |a| a.as_str());
+                (group_by, aggregate)
+            })
+        })
+    }
     /// simplify config for zoom level
     pub fn simplify(&self, level: u8) -> bool {
         let query_cfg = self.query_cfg(level, |q| q.simplify.is_some());
@@ -105,8 +479,56 @@ impl Layer {
         let query_cfg = self.query_cfg(level, |q| q.tolerance.is_some());
         query_cfg
             .and_then(|q| q.tolerance.as_ref())
+            .or_else(|| self.zoom_tolerance.get(&level))
             .unwrap_or(&self.tolerance)
     }
+    /// Distance of repeated points for zoom level
+    pub fn repeated_points_tolerance(&self, level: u8) -> &String {
+        self.repeated_points_tolerance
+            .as_ref()
+            .unwrap_or_else(|| self.tolerance(level))
+    }
+    /// Remove duplicate snapped points at zoom level
+    pub fn distinct_points(&self, level: u8) -> bool {
+        let point_layer = match self.base_geometry_type() {
+            Some("POINT") | Some("MULTIPOINT") => true,
+            _ => false,
+        };
+        point_layer && self.point_snap == Some(PointSnap::Distinct) && self.simplify(level)
+    }
+    /// SQL expression computing feature ids, e.g. for composite keys.
+    ///
+    /// A `fid_field` with other characters than letters, digits, `_` and `:` is an expression.
+    pub fn fid_expr(&self) -> Option<&String> {
+        self.fid_field.as_ref().filter(|fid| {
+            fid.chars()
+                .any(|c| !(c.is_alphanumeric() || c == '_' || c == ':'))
+        })
+    }
+    /// Attribute column is selected by `fields` and `exclude_fields`. The fid_field is always selected.
+    pub fn selects_field(&self, name: &str) -> bool {
+        if self.fid_field.as_ref().map_or(false, |fid| fid == name) {
+            return true;
+        }
+        (self.fields.is_empty() || self.fields.iter().any(|f| f.name == name))
+            && !self.exclude_fields.iter().any(|f| f == name)
+    }
+    /// Attribute name of a renamed column
+    pub fn field_alias(&self, name: &str) -> Option<&String> {
+        self.fields
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.alias.as_ref())
+    }
+    /// Column names of `order_by`
+    pub fn order_by_columns(&self) -> Vec<&str> {
+        self.order_by.as_ref().map_or(Vec::new(), |order_by| {
+            order_by
+                .split(',')
+                .filter_map(|item| item.split_whitespace().next())
+                .collect()
+        })
+    }
     /// Layer properties needed e.g. for metadata.json
     pub fn metadata(&self) -> HashMap<&str, String> {
         //TODO: return Zoom-Level Array
@@ -114,7 +536,7 @@ impl Layer {
         metadata.insert("id", self.name.clone());
         metadata.insert("name", self.name.clone());
         metadata.insert("description", "".to_string());
-        metadata.insert("buffer-size", self.buffer_size.unwrap_or(0).to_string());
+        metadata.insert("buffer-size", self.buffer_size.unwrap_or(0.0).to_string());
         metadata.insert("minzoom", self.minzoom().to_string());
         metadata.insert("maxzoom", self.maxzoom(22).to_string());
         metadata.insert("srs", "+proj=merc +a=6378137 +b=6378137 +lat_ts=0.0 +lon_0=0.0 +x_0=0.0 +y_0=0.0 +k=1.0 +units=m +nadgrids=@null +wktext +no_defs +over".to_string());
@@ -122,6 +544,123 @@ impl Layer {
     }
 }
 
+/// Check for undefined `!name!` variables in layer queries
+fn check_query_variables(
+    layer_name: &str,
+    queries: &Vec<LayerQuery>,
+    params: &BTreeMap<String, ParamValue>,
+) -> Result<(), String> {
+    let re = Regex::new(r"!([A-Za-z_][A-Za-z0-9_]*)!").unwrap();
+    let expressions = queries.iter().flat_map(|q| {
+        q.filter
+            .iter()
+            .chain(q.group_by.iter())
+            .chain(q.aggregate.iter())
+    });
+    for sql in queries
+        .iter()
+        .filter_map(|q| q.sql.as_ref())
+        .chain(expressions)
+    {
+        for cap in re.captures_iter(sql) {
+            let name = &cap[1];
+            if !QUERY_VARIABLES.contains(&name) && !params.contains_key(name) {
+                return Err(format!(
+                    "Layer '{}': unknown query variable '!{}!'",
+                    layer_name, name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check `filter` expressions, which must not end or escape the WHERE clause
+fn check_filters(layer_name: &str, queries: &Vec<LayerQuery>) -> Result<(), String> {
+    for filter in queries.iter().filter_map(|q| q.filter.as_ref()) {
+        let mut depth = 0;
+        for c in filter.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth < 0 {
+                break;
+            }
+        }
+        if depth != 0 || filter.contains(';') || filter.trim().is_empty() {
+            return Err(format!(
+                "Layer '{}': invalid filter '{}'",
+                layer_name, filter
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Geometry and fid columns are matched by name and can't be renamed
+fn check_field_aliases(layer_cfg: &LayerCfg, fields: &Vec<LayerField>) -> Result<(), String> {
+    let reserved: Vec<&String> = layer_cfg
+        .geometry_field
+        .iter()
+        .chain(
+            layer_cfg
+                .additional_geometry
+                .iter()
+                .map(|g| &g.geometry_field),
+        )
+        .chain(layer_cfg.fid_field.iter())
+        .collect();
+    for field in fields {
+        if let Some(ref alias) = field.alias {
+            if reserved.contains(&&field.name) {
+                return Err(format!(
+                    "Layer '{}': field '{}' can't be renamed",
+                    layer_cfg.name, field.name
+                ));
+            }
+            if reserved.contains(&alias) {
+                return Err(format!(
+                    "Layer '{}': alias '{}' conflicts with geometry_field or fid_field",
+                    layer_cfg.name, alias
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check `order_by` for a list of column names with optional sort directions
+fn check_order_by(layer_name: &str, order_by: &str) -> Result<(), String> {
+    let re =
+        Regex::new(r"(?i)^\s*[A-Za-z_][A-Za-z0-9_]*(\s+(ASC|DESC))?(\s+NULLS\s+(FIRST|LAST))?\s*$")
+            .unwrap();
+    for item in order_by.split(',') {
+        if !re.is_match(item) {
+            return Err(format!(
+                "Layer '{}': invalid order_by expression '{}'",
+                layer_name,
+                item.trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Geometry type without Z/M suffix (e.g. POINT for POINTZ)
+pub fn base_geometry_type(geometry_type: &str) -> &str {
+    geometry_type
+        .trim_end_matches('M')
+        .trim_end_matches('Z')
+        .trim_end()
+}
+
+/// Geometry type has a Z or M suffix
+pub fn has_zm_suffix(geometry_type: &str) -> bool {
+    geometry_type.ends_with('Z') || geometry_type.ends_with('M')
+}
+
 impl<'a> Config<'a, LayerCfg> for Layer {
     fn from_config(layer_cfg: &LayerCfg) -> Result<Self, String> {
         let queries = layer_cfg
@@ -133,6 +672,17 @@ impl<'a> Config<'a, LayerCfg> for Layer {
                 simplify: lq.simplify,
                 tolerance: lq.tolerance.clone(),
                 sql: lq.sql.clone(),
+                filter: lq.filter.clone(),
+                group_by: lq.group_by.clone(),
+                aggregate: lq.aggregate.clone(),
+            })
+            .collect();
+        let additional_geometry = layer_cfg
+            .additional_geometry
+            .iter()
+            .map(|g| LayerGeometry {
+                geometry_field: g.geometry_field.clone(),
+                geometry_type: g.geometry_type.clone(),
             })
             .collect();
         let style = match layer_cfg.style {
@@ -142,24 +692,113 @@ impl<'a> Config<'a, LayerCfg> for Layer {
             }
             None => None,
         };
+        let (tolerance, zoom_tolerance) = match layer_cfg.tolerance {
+            ToleranceCfg::Scalar(ref tolerance) => (tolerance.clone(), BTreeMap::new()),
+            ToleranceCfg::Zoom(ref tolerances) => {
+                let mut zoom_tolerance = BTreeMap::new();
+                for (zoom, tolerance) in tolerances {
+                    let level = zoom
+                        .parse::<u8>()
+                        .map_err(|_| format!("Invalid zoom level '{}' in tolerance", zoom))?;
+                    zoom_tolerance.insert(level, tolerance.clone());
+                }
+                (config::DEFAULT_TOLERANCE.to_string(), zoom_tolerance)
+            }
+        };
+        let buffer_units = match layer_cfg.buffer_units {
+            Some(ref buffer_units) => BufferUnits::from_str(buffer_units)?,
+            None => BufferUnits::Pixels,
+        };
+        let group_merge = match layer_cfg.group_merge {
+            Some(ref group_merge) => GroupMerge::from_str(group_merge)?,
+            None => GroupMerge::Collect,
+        };
+        let point_snap = match layer_cfg.point_snap {
+            Some(ref point_snap) => Some(PointSnap::from_str(point_snap)?),
+            None => None,
+        };
+        let mut params = BTreeMap::new();
+        for (name, value) in &layer_cfg.params {
+            if QUERY_VARIABLES.contains(&name.as_str()) {
+                return Err(format!(
+                    "Query parameter '{}' conflicts with built-in variable",
+                    name
+                ));
+            }
+            params.insert(name.clone(), ParamValue::from_config(name, value)?);
+        }
+        check_query_variables(&layer_cfg.name, &queries, &params)?;
+        check_filters(&layer_cfg.name, &queries)?;
+        let on_limit = match layer_cfg.on_limit {
+            Some(ref on_limit) => LimitMode::from_str(on_limit)?,
+            None => LimitMode::Truncate,
+        };
+        if let Some(ref order_by) = layer_cfg.order_by {
+            check_order_by(&layer_cfg.name, order_by)?;
+        }
+        let fields: Vec<LayerField> = layer_cfg
+            .fields
+            .iter()
+            .map(|field| match field {
+                LayerFieldCfg::Name(name) => LayerField::new(name),
+                LayerFieldCfg::Column { from, alias } => LayerField {
+                    name: from.clone(),
+                    alias: alias.clone(),
+                },
+            })
+            .collect();
+        check_field_aliases(layer_cfg, &fields)?;
+        if layer_cfg.distinct_on && layer_cfg.fid_field.is_none() {
+            return Err(format!(
+                "Layer '{}': distinct_on requires fid_field",
+                layer_cfg.name
+            ));
+        }
         Ok(Layer {
             name: layer_cfg.name.clone(),
             datasource: layer_cfg.datasource.clone(), //TODO: inherit from parents if None?
             geometry_field: layer_cfg.geometry_field.clone(),
             geometry_type: layer_cfg.geometry_type.clone(),
+            dimensions: layer_cfg.dimensions,
+            additional_geometry: additional_geometry,
             srid: layer_cfg.srid,
+            assume_srid: layer_cfg.assume_srid,
             no_transform: layer_cfg.no_transform,
+            output_srid: layer_cfg.output_srid,
+            no_index_check: layer_cfg.no_index_check,
             fid_field: layer_cfg.fid_field.clone(),
+            distinct_on: layer_cfg.distinct_on,
+            fields: fields,
+            exclude_fields: layer_cfg.exclude_fields.clone(),
             table_name: layer_cfg.table_name.clone(),
             query_limit: layer_cfg.query_limit,
+            on_limit: on_limit,
+            hard_limit: layer_cfg.hard_limit,
+            order_by: layer_cfg.order_by.clone(),
+            query_timeout: layer_cfg.query_timeout,
+            fetch_size: layer_cfg.fetch_size,
+            params: params,
             query: queries,
             minzoom: layer_cfg.minzoom,
             maxzoom: layer_cfg.maxzoom,
             tile_size: layer_cfg.tile_size,
             simplify: layer_cfg.simplify,
-            tolerance: layer_cfg.tolerance.clone(),
+            tolerance: tolerance,
+            zoom_tolerance: zoom_tolerance,
+            point_snap: point_snap,
+            remove_repeated_points: layer_cfg.remove_repeated_points,
+            repeated_points_tolerance: layer_cfg.repeated_points_tolerance.clone(),
+            curve_tolerance: layer_cfg.curve_tolerance.clone(),
+            min_area: layer_cfg.min_area.clone(),
+            min_length: layer_cfg.min_length.clone(),
+            group_merge: group_merge,
             buffer_size: layer_cfg.buffer_size,
+            buffer_units: buffer_units,
+            clip_buffer_zero: layer_cfg.clip_buffer_zero,
+            skip_empty: layer_cfg.skip_empty,
             make_valid: layer_cfg.make_valid,
+            make_valid_params: layer_cfg.make_valid_params.clone(),
+            force_multi: layer_cfg.force_multi,
             shift_longitude: layer_cfg.shift_longitude,
             style: style,
         })
@@ -173,6 +812,8 @@ name = "points"
 #maxzoom = 22
 #attribution = "© Contributeurs de OpenStreetMap" # Acknowledgment of ownership, authorship or copyright.
 #cache_limits = {minzoom = 0, maxzoom = 22, no_cache = false}
+#metatile = 4 # Render blocks of 4x4 tiles with one query per layer when seeding
+#max_features = 20000 # Maximum number of features of all layers in a tile
 
 [[tileset.layer]]
 name = "points"
@@ -181,7 +822,9 @@ geometry_field = "wkb_geometry"
 geometry_type = "POINT"
 #simplify = true
 #tolerance = "!pixel_width!/2"
+#point_snap = "distinct" # Snap points to tolerance grid and remove duplicates when simplifying
 #buffer_size = 10
+#min_area = "!pixel_width!*!pixel_width!" # Skip polygons smaller than a pixel
 #make_valid = true
 #[[tileset.layer.query]]
 #minzoom = 0
@@ -221,8 +864,33 @@ geometry_type = "POINT"
         if self.no_transform {
             lines.push(format!("no_transform = true"));
         }
+        if let Some(assume_srid) = self.assume_srid {
+            lines.push(format!("assume_srid = {}", assume_srid));
+        }
+        if let Some(output_srid) = self.output_srid {
+            lines.push(format!("output_srid = {}", output_srid));
+        }
+        if self.no_index_check {
+            lines.push(format!("no_index_check = true"));
+        }
         if let Some(ref fid_field) = self.fid_field {
-            lines.push(format!("fid_field = \"{}\"", fid_field));
+            lines.push(format!("fid_field = {}", toml_string(fid_field)));
+        }
+        if self.distinct_on {
+            lines.push("distinct_on = true".to_string());
+        }
+        if self.fields.iter().any(|f| f.alias.is_some()) {
+            let fields: Vec<String> = self.fields.iter().map(|f| f.to_config()).collect();
+            lines.push(format!("fields = [{}]", fields.join(", ")));
+        } else if !self.fields.is_empty() {
+            let fields: Vec<&String> = self.fields.iter().map(|f| &f.name).collect();
+            lines.push(format!("fields = {}", toml_string_array(&fields)));
+        }
+        if !self.exclude_fields.is_empty() {
+            lines.push(format!(
+                "exclude_fields = {}",
+                toml_string_array(&self.exclude_fields)
+            ));
         }
         if self.tile_size != 4096 {
             lines.push(format!(r#"tile_size = "{}""#, self.tile_size));
@@ -231,31 +899,133 @@ geometry_type = "POINT"
             Some(ref buffer_size) => lines.push(format!("buffer_size = {}", buffer_size)),
             _ => lines.push(format!("#buffer_size = 10")),
         }
+        if self.buffer_units != BufferUnits::Pixels {
+            lines.push(format!("buffer_units = \"{}\"", self.buffer_units.as_str()));
+        }
+        if !self.clip_buffer_zero {
+            lines.push(format!("clip_buffer_zero = false"));
+        }
+        if !self.skip_empty {
+            lines.push(format!("skip_empty = false"));
+        }
         match self.make_valid {
             true => lines.push(format!("make_valid = true")),
             _ => lines.push(format!("#make_valid = true")),
         }
+        if let Some(ref make_valid_params) = self.make_valid_params {
+            lines.push(format!(
+                "make_valid_params = {}",
+                toml_string(make_valid_params)
+            ));
+        }
+        if let Some(dimensions) = self.dimensions {
+            lines.push(format!("dimensions = {}", dimensions));
+        }
+        if !self.force_multi {
+            lines.push(format!("force_multi = false"));
+        }
         if self.shift_longitude {
             lines.push(format!("shift_longitude = true"));
         }
-        if self.geometry_type != Some("POINT".to_string()) {
-            // simplify is ignored for points
+        if self.base_geometry_type() != Some("POINT") || self.point_snap.is_some() {
+            // simplify is ignored for points without point_snap
             lines.push(format!("simplify = {}", self.simplify));
-            if self.simplify && self.tolerance != config::DEFAULT_TOLERANCE {
+            if self.simplify && !self.zoom_tolerance.is_empty() {
+                let tolerances: Vec<String> = self
+                    .zoom_tolerance
+                    .iter()
+                    .map(|(zoom, tolerance)| format!("{} = \"{}\"", zoom, tolerance))
+                    .collect();
+                lines.push(format!("tolerance = {{ {} }}", tolerances.join(", ")));
+            } else if self.simplify && self.tolerance != config::DEFAULT_TOLERANCE {
                 lines.push(format!("tolerance = \"{}\"", self.tolerance));
             }
         }
+        if let Some(ref point_snap) = self.point_snap {
+            lines.push(format!("point_snap = \"{}\"", point_snap.as_str()));
+        }
+        if self.remove_repeated_points {
+            lines.push("remove_repeated_points = true".to_string());
+        }
+        if let Some(ref repeated_points_tolerance) = self.repeated_points_tolerance {
+            lines.push(format!(
+                "repeated_points_tolerance = \"{}\"",
+                repeated_points_tolerance
+            ));
+        }
+        if let Some(ref curve_tolerance) = self.curve_tolerance {
+            lines.push(format!("curve_tolerance = \"{}\"", curve_tolerance));
+        }
+        if let Some(ref min_area) = self.min_area {
+            lines.push(format!("min_area = \"{}\"", min_area));
+        }
+        if let Some(ref min_length) = self.min_length {
+            lines.push(format!("min_length = \"{}\"", min_length));
+        }
+        if self.group_merge != GroupMerge::Collect {
+            lines.push(format!("group_merge = \"{}\"", self.group_merge.as_str()));
+        }
         match self.query_limit {
             Some(ref query_limit) => lines.push(format!("query_limit = {}", query_limit)),
             _ => lines.push("#query_limit = 1000".to_string()),
         }
-        match self.query(0) {
-            Some(ref query) => {
-                lines.push("[[tileset.layer.query]]".to_string());
-                lines.push(format!("sql = \"{}\"", query))
+        if self.on_limit != LimitMode::Truncate {
+            lines.push(format!("on_limit = \"{}\"", self.on_limit.as_str()));
+        }
+        if let Some(hard_limit) = self.hard_limit {
+            lines.push(format!("hard_limit = {}", hard_limit));
+        }
+        if let Some(ref order_by) = self.order_by {
+            lines.push(format!("order_by = {}", toml_string(order_by)));
+        }
+        if let Some(query_timeout) = self.query_timeout {
+            lines.push(format!("query_timeout = {}", query_timeout));
+        }
+        if let Some(fetch_size) = self.fetch_size {
+            lines.push(format!("fetch_size = {}", fetch_size));
+        }
+        if !self.params.is_empty() {
+            let params: Vec<String> = self
+                .params
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value.to_config()))
+                .collect();
+            lines.push(format!("params = {{ {} }}", params.join(", ")));
+        }
+        for geom in &self.additional_geometry {
+            lines.push("[[tileset.layer.additional_geometry]]".to_string());
+            lines.push(format!("geometry_field = \"{}\"", geom.geometry_field));
+            if let Some(ref geometry_type) = geom.geometry_type {
+                lines.push(format!("geometry_type = \"{}\"", geometry_type));
+            }
+        }
+        if self.query.is_empty() {
+            lines.push("#[[tileset.layer.query]]".to_string());
+        }
+        for query in &self.query {
+            lines.push("[[tileset.layer.query]]".to_string());
+            lines.push(format!("minzoom = {}", query.minzoom));
+            if let Some(maxzoom) = query.maxzoom {
+                lines.push(format!("maxzoom = {}", maxzoom));
+            }
+            if let Some(simplify) = query.simplify {
+                lines.push(format!("simplify = {}", simplify));
+            }
+            if let Some(ref tolerance) = query.tolerance {
+                lines.push(format!("tolerance = {}", toml_string(tolerance)));
+            }
+            if let Some(ref sql) = query.sql {
+                lines.push(format!("sql = \"\"\"{}\"\"\"", sql));
             }
-            _ => {
-                lines.push("#[[tileset.layer.query]]".to_string());
+            let expressions = [
+                ("filter", &query.filter),
+                ("group_by", &query.group_by),
+                ("aggregate", &query.aggregate),
+            ];
+            for (key, expr) in expressions.iter() {
+                if let Some(expr) = expr {
+                    lines.push(format!("{} = {}", key, toml_string(expr)));
+                }
             }
         }
         lines.join("\n") + "\n"
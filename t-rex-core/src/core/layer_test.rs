@@ -4,7 +4,7 @@
 //
 
 use crate::core::config::Config;
-use crate::core::layer::Layer;
+use crate::core::layer::{BufferUnits, GroupMerge, Layer, LimitMode, ParamValue, PointSnap};
 use crate::service::tileset::Tileset;
 
 fn layer_from_config(toml: &str) -> Result<Layer, String> {
@@ -90,6 +90,501 @@ fn test_layer_defaults() {
     assert_eq!(cfg.query.len(), 0);
     assert_eq!(cfg.minzoom(), 0);
     assert_eq!(cfg.maxzoom(30), 30);
+    assert!(cfg.force_multi);
+    assert!(cfg.clip_buffer_zero);
+    assert!(Layer::new("points").clip_buffer_zero);
+    assert!(cfg.skip_empty);
+    assert!(Layer::new("points").skip_empty);
+}
+
+#[test]
+fn test_additional_geometry_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "buildings"
+        table_name = "buildings"
+        geometry_field = "geom_area"
+        geometry_type = "POLYGON"
+        #[[tileset.layer.additional_geometry]]
+        [[additional_geometry]]
+        geometry_field = "geom_point"
+        geometry_type = "POINT"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.additional_geometry.len(), 1);
+    assert_eq!(cfg.additional_geometry[0].geometry_field, "geom_point");
+    assert_eq!(cfg.geometry_fields(), vec!["geom_area", "geom_point"]);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("[[tileset.layer.additional_geometry]]\ngeometry_field = \"geom_point\"\ngeometry_type = \"POINT\"\n"));
+}
+
+#[test]
+fn test_query_runtime_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        geometry_field = "wkb_geometry"
+        [[query]]
+        sql = 'SELECT "Name"::TEXT,wkb_geometry FROM places WHERE wkb_geometry && !bbox!'
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert!(cfg.gen_runtime_config().contains(
+        r#"[[tileset.layer.query]]
+minzoom = 0
+sql = """SELECT "Name"::TEXT,wkb_geometry FROM places WHERE wkb_geometry && !bbox!""""#
+    ));
+}
+
+#[test]
+fn test_zoom_queries_runtime_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "rivers"
+        table_name = "rivers"
+        #[[tileset.layer.query]]
+        [[query]]
+        maxzoom = 7
+        simplify = true
+        tolerance = "!pixel_width!*2"
+        filter = "scalerank <= 4"
+        #[[tileset.layer.query]]
+        [[query]]
+        minzoom = 8
+        maxzoom = 14
+        sql = "SELECT name,geometry FROM rivers_z8"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    let runtime_cfg = cfg.gen_runtime_config();
+    assert!(runtime_cfg.ends_with(
+        r#"[[tileset.layer.query]]
+minzoom = 0
+maxzoom = 7
+simplify = true
+tolerance = "!pixel_width!*2"
+filter = "scalerank <= 4"
+[[tileset.layer.query]]
+minzoom = 8
+maxzoom = 14
+sql = """SELECT name,geometry FROM rivers_z8"""
+"#
+    ));
+    let cfg2 = layer_from_config(
+        &runtime_cfg
+            .replace("[[tileset.layer.query]]", "[[query]]")
+            .replace("[[tileset.layer]]", ""),
+    )
+    .unwrap();
+    assert_eq!(cfg2.query.len(), 2);
+    assert_eq!(cfg2.filter(5), cfg.filter(5));
+    assert_eq!(cfg2.simplify(5), cfg.simplify(5));
+    assert_eq!(cfg2.tolerance(5), cfg.tolerance(5));
+    assert_eq!(cfg2.query(10), cfg.query(10));
+    assert_eq!(cfg2.maxzoom(22), 14);
+}
+
+#[test]
+fn test_on_limit_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        query_limit = 100
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.on_limit, LimitMode::Truncate);
+    assert!(!cfg.gen_runtime_config().contains("on_limit"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        query_limit = 100
+        on_limit = "sample"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.on_limit, LimitMode::Sample);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("query_limit = 100\non_limit = \"sample\"\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        on_limit = "drop"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Unexpected enum value 'drop'".to_string())
+    );
+}
+
+#[test]
+fn test_assume_srid_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "parcels"
+        srid = 0
+        assume_srid = 2056
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.known_srid(), Some(2056));
+    assert!(cfg.srid_assumed());
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("srid = 0\nassume_srid = 2056\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "parcels"
+        srid = 21781
+        assume_srid = 2056
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.known_srid(), Some(21781));
+    assert!(!cfg.srid_assumed());
+}
+
+#[test]
+fn test_hard_limit_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        query_limit = 100
+        hard_limit = 1000
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.query_limit, Some(100));
+    assert_eq!(cfg.hard_limit, Some(1000));
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("query_limit = 100\nhard_limit = 1000\n"));
+}
+
+#[test]
+fn test_order_by_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        table_name = "ne_10m_populated_places"
+        query_limit = 100
+        order_by = "pop_max DESC NULLS LAST, name"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.order_by_columns(), vec!["pop_max", "name"]);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("query_limit = 100\norder_by = \"pop_max DESC NULLS LAST, name\"\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        order_by = "pop_max; DROP TABLE places"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'places': invalid order_by expression 'pop_max; DROP TABLE places'".to_string()
+        )
+    );
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        order_by = "ST_Area(geometry) DESC"
+        "#;
+    assert!(layer_from_config(toml).is_err());
+
+    // Layers created without config validation
+    let mut layer = Layer::new("places");
+    layer.order_by = Some(r#""Pop Max" DESC"#.to_string());
+    assert!(layer
+        .gen_runtime_config()
+        .contains(r#"order_by = "\"Pop Max\" DESC""#));
+}
+
+#[test]
+fn test_distinct_on_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "roads"
+        table_name = "road_tags"
+        fid_field = "osm_id"
+        distinct_on = true
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert!(cfg.distinct_on);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("fid_field = \"osm_id\"\ndistinct_on = true\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "roads"
+        table_name = "road_tags"
+        distinct_on = true
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'roads': distinct_on requires fid_field".to_string())
+    );
+}
+
+#[test]
+fn test_fields_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        table_name = "ne_10m_populated_places"
+        fid_field = "fid"
+        fields = ["name", "pop_max"]
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert!(cfg.selects_field("name"));
+    assert!(cfg.selects_field("fid"));
+    assert!(!cfg.selects_field("scalerank"));
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("fields = [\"name\", \"pop_max\"]\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        table_name = "ne_10m_populated_places"
+        fid_field = "fid"
+        exclude_fields = ["fid", "scalerank"]
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert!(cfg.selects_field("name"));
+    assert!(cfg.selects_field("fid"));
+    assert!(!cfg.selects_field("scalerank"));
+}
+
+#[test]
+fn test_field_alias_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        table_name = "ne_10m_populated_places"
+        fields = [{ from = "name" }, { from = "pop_2020_est", as = "pop" }]
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert!(cfg.selects_field("pop_2020_est"));
+    assert_eq!(cfg.field_alias("pop_2020_est"), Some(&"pop".to_string()));
+    assert_eq!(cfg.field_alias("name"), None);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("fields = [{ from = \"name\" }, { from = \"pop_2020_est\", as = \"pop\" }]\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        geometry_field = "wkb_geometry"
+        fid_field = "fid"
+        fields = [{ from = "name", as = "fid" }]
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'places': alias 'fid' conflicts with geometry_field or fid_field".to_string())
+    );
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        geometry_field = "wkb_geometry"
+        fields = [{ from = "wkb_geometry", as = "geom" }]
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'places': field 'wkb_geometry' can't be renamed".to_string())
+    );
+}
+
+#[test]
+fn test_filter_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "rivers"
+        table_name = "rivers"
+        #[[tileset.layer.query]]
+        [[query]]
+        maxzoom = 7
+        filter = "scalerank <= 4"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.filter(5), Some(&"scalerank <= 4".to_string()));
+    assert_eq!(cfg.filter(8), None);
+    assert_eq!(cfg.query(5), None);
+    assert!(cfg.gen_runtime_config().ends_with(
+        "[[tileset.layer.query]]\nminzoom = 0\nmaxzoom = 7\nfilter = \"scalerank <= 4\"\n"
+    ));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "rivers"
+        #[[tileset.layer.query]]
+        [[query]]
+        filter = "scalerank <= 4); DROP TABLE rivers; --"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'rivers': invalid filter 'scalerank <= 4); DROP TABLE rivers; --'".to_string())
+    );
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "rivers"
+        #[[tileset.layer.query]]
+        [[query]]
+        filter = "scalerank <= !max_rank!"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'rivers': unknown query variable '!max_rank!'".to_string())
+    );
+}
+
+#[test]
+fn test_buffer_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        buffer_size = 0.5
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.buffer_size, Some(0.5));
+    assert_eq!(cfg.buffer_units, BufferUnits::Pixels);
+    assert_eq!(cfg.buffer_width(10.0), 5.0);
+    assert!(cfg.gen_runtime_config().contains("buffer_size = 0.5\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        buffer_size = 250
+        buffer_units = "ground"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.buffer_units, BufferUnits::Ground);
+    assert_eq!(cfg.buffer_width(10.0), 250.0);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("buffer_size = 250\nbuffer_units = \"ground\"\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        buffer_units = "meters"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Unexpected enum value 'meters'".to_string())
+    );
+}
+
+#[test]
+fn test_group_by_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        table_name = "ne_10m_populated_places"
+        #[[tileset.layer.query]]
+        [[query]]
+        maxzoom = 8
+        group_by = "ST_SnapToGrid(wkb_geometry, !pixel_width!*32)"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(
+        cfg.group_by(5),
+        Some((
+            &"ST_SnapToGrid(wkb_geometry, !pixel_width!*32)".to_string(),
+            "count(*) AS count"
+        ))
+    );
+    assert_eq!(cfg.group_by(9), None);
+    assert!(cfg.gen_runtime_config().ends_with(
+        "[[tileset.layer.query]]\nminzoom = 0\nmaxzoom = 8\ngroup_by = \"ST_SnapToGrid(wkb_geometry, !pixel_width!*32)\"\n"
+    ));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "places"
+        #[[tileset.layer.query]]
+        [[query]]
+        group_by = "ST_SnapToGrid(wkb_geometry, !pixel_width!*32)"
+        aggregate = "count(*) AS count, sum(!pop_max!) AS pop_max"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'places': unknown query variable '!pop_max!'".to_string())
+    );
+}
+
+#[test]
+fn test_query_timeout_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.query_timeout, None);
+    assert!(!cfg.gen_runtime_config().contains("query_timeout"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        query_timeout = 5000
+        fetch_size = 1000
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.query_timeout, Some(5000));
+    assert_eq!(cfg.fetch_size, Some(1000));
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("query_timeout = 5000\nfetch_size = 1000\n"));
+}
+
+#[test]
+fn test_params_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "buildings"
+        geometry_field = "way"
+        params = { tenant = "acme", min_height = 3 }
+        [[query]]
+        sql = "SELECT name,way FROM buildings WHERE tenant=!tenant! AND height>=!min_height! AND way && !bbox!"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(
+        cfg.params.get("tenant"),
+        Some(&ParamValue::String("acme".to_string()))
+    );
+    assert_eq!(cfg.params.get("min_height"), Some(&ParamValue::Int(3)));
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("params = { min_height = 3, tenant = \"acme\" }\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "buildings"
+        params = { tenant = "acme" }
+        [[query]]
+        sql = "SELECT name,way FROM buildings WHERE tenant=!tennant!"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'buildings': unknown query variable '!tennant!'".to_string())
+    );
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "buildings"
+        params = { zoom = 3 }
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Query parameter 'zoom' conflicts with built-in variable".to_string())
+    );
 }
 
 #[test]
@@ -258,6 +753,121 @@ fn test_simplify_config() {
     assert_eq!(cfg.tolerance(14), "!pixel_width!/5"); // should it be "!pixel_width!/6" ?
 }
 
+#[test]
+fn test_zoom_tolerance_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "roads"
+        geometry_field = "wkb_geometry"
+        geometry_type = "LINESTRING"
+        simplify = true
+        tolerance = { 4 = "!pixel_width!", 14 = "0.5" }
+        [[query]]
+        minzoom = 16
+        tolerance = "0"
+        sql = "SELECT name,wkb_geometry FROM roads"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.tolerance(4), "!pixel_width!");
+    assert_eq!(cfg.tolerance(14), "0.5");
+    // Fallback to default tolerance
+    assert_eq!(cfg.tolerance(10), "!pixel_width!/2");
+    // Query tolerance
+    assert_eq!(cfg.tolerance(16), "0");
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("tolerance = { 4 = \"!pixel_width!\", 14 = \"0.5\" }\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "roads"
+        tolerance = { z4 = "!pixel_width!" }
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Invalid zoom level 'z4' in tolerance".to_string())
+    );
+}
+
+#[test]
+fn test_point_snap_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "gps"
+        table_name = "gps_points"
+        geometry_field = "wkb_geometry"
+        geometry_type = "POINT"
+        simplify = true
+        point_snap = "distinct"
+        [[query]]
+        minzoom = 14
+        simplify = false
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.point_snap, Some(PointSnap::Distinct));
+    assert!(cfg.distinct_points(10));
+    assert!(!cfg.distinct_points(14));
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("simplify = true\npoint_snap = \"distinct\"\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "gps"
+        geometry_type = "POINT"
+        simplify = true
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.point_snap, None);
+    assert!(!cfg.gen_runtime_config().contains("simplify"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "gps"
+        point_snap = "cluster"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Unexpected enum value 'cluster'".to_string())
+    );
+}
+
+#[test]
+fn test_group_merge_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "countries"
+        geometry_type = "POLYGON"
+        group_merge = "union"
+        [[query]]
+        maxzoom = 4
+        group_by = "continent"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.group_merge, GroupMerge::Union);
+    assert!(cfg
+        .gen_runtime_config()
+        .contains("group_merge = \"union\"\n"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "countries"
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    assert_eq!(cfg.group_merge, GroupMerge::Collect);
+    assert!(!cfg.gen_runtime_config().contains("group_merge"));
+
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "countries"
+        group_merge = "dissolve"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Unexpected enum value 'dissolve'".to_string())
+    );
+}
+
 #[test]
 fn test_invalid_configs() {
     // Invalid config: missing required field
@@ -332,7 +942,7 @@ fn test_layers_from_config() {
         layers[0].table_name,
         Some("ne_10m_populated_places".to_string())
     );
-    assert_eq!(layers[0].buffer_size, Some(10));
+    assert_eq!(layers[0].buffer_size, Some(10.0));
     assert_eq!(layers[1].table_name, None);
     assert_eq!(layers[1].buffer_size, None); // serde distincts between '-' and '_'
 
@@ -343,3 +953,47 @@ fn test_layers_from_config() {
         Some(" - missing field `name`".to_string())
     );
 }
+
+#[test]
+fn test_fid_expr() {
+    let mut layer = Layer::new("roads");
+    assert_eq!(layer.fid_expr(), None);
+    layer.fid_field = Some(String::from("osm_id"));
+    assert_eq!(layer.fid_expr(), None);
+    layer.fid_field = Some(String::from("ref:id"));
+    assert_eq!(layer.fid_expr(), None);
+    layer.fid_field = Some(String::from("row_number() over()"));
+    assert_eq!(layer.fid_expr(), layer.fid_field.as_ref());
+}
+
+#[test]
+fn test_fid_expr_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "roads"
+        fid_field = 'concat("osm_id", "type")'
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    let runtime_cfg = cfg.gen_runtime_config();
+    assert!(runtime_cfg.contains(r#"fid_field = "concat(\"osm_id\", \"type\")""#));
+    let cfg2 = layer_from_config(&runtime_cfg.replace("[[tileset.layer]]", "")).unwrap();
+    assert_eq!(cfg2.fid_field, cfg.fid_field);
+}
+
+#[test]
+fn test_make_valid_config() {
+    let toml = r#"
+        #[[tileset.layer]]
+        name = "parcels"
+        make_valid = true
+        make_valid_params = 'method=structure "keepcollapsed=false"'
+        "#;
+    let cfg = layer_from_config(toml).unwrap();
+    let runtime_cfg = cfg.gen_runtime_config();
+    assert!(runtime_cfg.contains(
+        r#"make_valid = true
+make_valid_params = "method=structure \"keepcollapsed=false\"""#
+    ));
+    let cfg2 = layer_from_config(&runtime_cfg.replace("[[tileset.layer]]", "")).unwrap();
+    assert_eq!(cfg2.make_valid_params, cfg.make_valid_params);
+}
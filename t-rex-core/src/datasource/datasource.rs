@@ -7,20 +7,90 @@ use crate::core::config::DatasourceCfg;
 use crate::core::feature::Feature;
 use crate::core::layer::Layer;
 use crate::core::Config;
+use crate::mvt::vector_tile;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tile_grid::Extent;
 use tile_grid::Grid;
 
+/// Error retrieving features
+#[derive(PartialEq, Clone, Debug)]
+pub enum DatasourceError {
+    /// Connection or query failure
+    Query(String),
+    /// Layer with `on_limit = "error"` exceeded its `query_limit`
+    QueryLimitExceeded,
+}
+
+impl fmt::Display for DatasourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatasourceError::Query(err) => write!(f, "{}", err),
+            DatasourceError::QueryLimitExceeded => write!(f, "query_limit exceeded"),
+        }
+    }
+}
+
+/// Number of features left for all layers of a tile (`max_features`)
+///
+/// Clones share the same counter.
+#[derive(Clone, Debug)]
+pub struct FeatureBudget {
+    remaining: Arc<AtomicU64>,
+}
+
+impl FeatureBudget {
+    pub fn new(max_features: u64) -> FeatureBudget {
+        FeatureBudget {
+            remaining: Arc::new(AtomicU64::new(max_features)),
+        }
+    }
+    /// Take one feature from the budget. Returns `false` if the budget is exhausted.
+    pub fn take(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+    /// Take `count` features, e.g. of a layer encoded by the datasource.
+    pub fn consume(&self, count: u64) {
+        let _ = self
+            .remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                Some(n.saturating_sub(count))
+            });
+    }
+    pub fn remaining(&self) -> u64 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
 pub trait DatasourceType {
     /// New instance with connected pool
     fn connected(&self) -> Self;
     fn detect_layers(&self, detect_geometry_types: bool) -> Vec<Layer>;
     /// Return column field names and Rust compatible type conversion - without geometry column
     fn detect_data_columns(&self, layer: &Layer, sql: Option<&String>) -> Vec<(String, String)>;
+    /// Layer extent in WGS84
+    ///
+    /// The extent of `no_transform` layers treats layer coordinates as grid coordinates,
+    /// so projecting it with `extent_from_wgs84` to the grid SRS returns the queried layer
+    /// coordinates without a further transformation.
     fn layer_extent(&self, layer: &Layer, grid_srid: i32) -> Option<Extent>;
-    fn prepare_queries(&mut self, tileset: &str, layer: &Layer, grid_srid: i32);
+    fn prepare_queries(&mut self, tileset: &str, layer: &Layer, grid: &Grid);
+    /// Check prepared queries of a layer against the datasource. Return error messages.
+    fn validate_queries(&self, _tileset: &str, _layer: &Layer) -> Vec<String> {
+        Vec::new()
+    }
     /// Projected extent
     fn extent_from_wgs84(&self, extent: &Extent, dest_srid: i32) -> Option<Extent>;
     /// Retrieve features of one layer. Return feature count.
+    ///
+    /// Errors of single features don't fail the whole layer.
+    /// Reading stops early when the `budget` shared by the caller is exhausted.
     fn retrieve_features<F>(
         &self,
         tileset: &str,
@@ -28,10 +98,24 @@ pub trait DatasourceType {
         extent: &Extent,
         zoom: u8,
         grid: &Grid,
+        budget: Option<&FeatureBudget>,
         read: F,
-    ) -> u64
+    ) -> Result<u64, DatasourceError>
     where
         F: FnMut(&dyn Feature);
+    /// Retrieve layer encoded by the datasource. Return `None` if not supported.
+    ///
+    /// Connection and query failures are returned as errors, not as empty layers.
+    fn retrieve_mvt_layer(
+        &self,
+        _tileset: &str,
+        _layer: &Layer,
+        _extent: &Extent,
+        _zoom: u8,
+        _grid: &Grid,
+    ) -> Option<Result<vector_tile::Tile_Layer, DatasourceError>> {
+        None
+    }
 }
 
 pub struct DummyDatasource;
@@ -52,7 +136,7 @@ impl DatasourceType for DummyDatasource {
     fn layer_extent(&self, _layer: &Layer, _grid_srid: i32) -> Option<Extent> {
         unimplemented!();
     }
-    fn prepare_queries(&mut self, _tileset: &str, _layer: &Layer, _grid_srid: i32) {}
+    fn prepare_queries(&mut self, _tileset: &str, _layer: &Layer, _grid: &Grid) {}
     fn retrieve_features<F>(
         &self,
         _tileset: &str,
@@ -60,12 +144,13 @@ impl DatasourceType for DummyDatasource {
         _extent: &Extent,
         _zoom: u8,
         _grid: &Grid,
+        _budget: Option<&FeatureBudget>,
         _read: F,
-    ) -> u64
+    ) -> Result<u64, DatasourceError>
     where
         F: FnMut(&dyn Feature),
     {
-        0
+        Ok(0)
     }
 }
 
@@ -86,3 +171,20 @@ impl<'a> Config<'a, DatasourceCfg> for DummyDatasource {
         "".to_string()
     }
 }
+
+#[test]
+fn test_feature_budget() {
+    let budget = FeatureBudget::new(2);
+    let shared = budget.clone();
+    assert!(budget.take());
+    assert!(shared.take());
+    assert!(!budget.take());
+    assert!(shared.is_exhausted());
+
+    let budget = FeatureBudget::new(10);
+    budget.consume(4);
+    assert_eq!(budget.remaining(), 6);
+    budget.consume(8);
+    assert_eq!(budget.remaining(), 0);
+    assert!(!budget.take());
+}
@@ -9,5 +9,5 @@ mod postgis_fields;
 #[cfg(test)]
 mod postgis_test;
 
-pub use self::datasource::{DatasourceType, DummyDatasource};
-pub use self::postgis_ds::PostgisDatasource;
+pub use self::datasource::{DatasourceError, DatasourceType, DummyDatasource, FeatureBudget};
+pub use self::postgis_ds::{DatasourceCaps, LayerInfo, MvtMode, PostgisDatasource, SslMode};
@@ -3,34 +3,75 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::core::feature::{Feature, FeatureAttr, FeatureAttrValType};
+use crate::core::feature::{fid_hash, Feature, FeatureAttr, FeatureAttrValType};
 use crate::core::geom::*;
-use crate::core::layer::Layer;
+use crate::core::layer::{base_geometry_type, Layer};
+use fallible_iterator::FallibleIterator;
+use postgis::ewkb;
 use postgres::rows::Row;
-use postgres::types::{self, FromSql, Type};
+use postgres::types::{self, FromSql, Kind, Type};
+use postgres_protocol::types::{array_from_sql, date_from_sql, timestamp_from_sql, uuid_from_sql};
 use std;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Days between 1970-01-01 (Unix epoch) and 2000-01-01 (PostgreSQL epoch)
+const PG_EPOCH_DAYS: i64 = 10957;
+
+/// Column with `ST_GeometryType` of the source geometry in `GEOMETRY` layers
+pub(crate) const GEOMETRY_TYPE_COLUMN: &str = "_geometry_type";
+
+/// Column name of feature ids computed by a `fid_field` expression
+pub(crate) const FID_COLUMN: &str = "_fid";
+
+/// Column with feature ids of a layer
+pub(crate) fn fid_column(layer: &Layer) -> Option<&str> {
+    if layer.fid_expr().is_some() {
+        Some(FID_COLUMN)
+    } else {
+        layer.fid_field.as_ref().map(|fid| fid.as_str())
+    }
+}
 
 impl GeometryType {
     /// Convert returned geometry to core::geom::GeometryType based on GeometryType name
-    pub fn from_geom_field(row: &Row, idx: &str, type_name: &str) -> Result<GeometryType, String> {
+    ///
+    /// Lines and polygons are expected as multi geometries, if `force_multi` is set.
+    /// Z/M ordinates are only supported for points (`POINTZ`).
+    pub fn from_geom_field(
+        row: &Row,
+        idx: &str,
+        type_name: &str,
+        force_multi: bool,
+    ) -> Result<GeometryType, String> {
         let field = match type_name {
             //Option<Result<T>> --> Option<Result<GeometryType>>
             "POINT" => row
                 .get_opt::<_, Point>(idx)
                 .map(|opt| opt.map(|f| GeometryType::Point(f))),
-            //"LINESTRING" =>
-            //    row.get_opt::<_, LineString>(idx).map(|opt| opt.map(|f| GeometryType::LineString(f))),
-            //"POLYGON" =>
-            //    row.get_opt::<_, Polygon>(idx).map(|opt| opt.map(|f| GeometryType::Polygon(f))),
+            "POINTZ" => row
+                .get_opt::<_, PointZ>(idx)
+                .map(|opt| opt.map(|f| GeometryType::PointZ(f))),
+            // Single geometries may be converted to multi geometries by clipping or validation
+            "LINESTRING" | "COMPOUNDCURVE" | "CIRCULARSTRING" | "POLYGON" | "CURVEPOLYGON"
+                if !force_multi =>
+            {
+                row.get_opt::<_, ewkb::Geometry>(idx)
+                    .map(|opt| opt.map(|f| GeometryType::from(f)))
+            }
             "MULTIPOINT" => row
                 .get_opt::<_, MultiPoint>(idx)
                 .map(|opt| opt.map(|f| GeometryType::MultiPoint(f))),
-            "LINESTRING" | "MULTILINESTRING" | "COMPOUNDCURVE" => row
+            "LINESTRING" | "MULTILINESTRING" | "COMPOUNDCURVE" | "CIRCULARSTRING"
+            | "MULTICURVE" => row
                 .get_opt::<_, MultiLineString>(idx)
                 .map(|opt| opt.map(|f| GeometryType::MultiLineString(f))),
-            "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" => row
+            "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" | "MULTISURFACE" => row
                 .get_opt::<_, MultiPolygon>(idx)
                 .map(|opt| opt.map(|f| GeometryType::MultiPolygon(f))),
+            "GEOMETRY" => row
+                .get_opt::<_, ewkb::Geometry>(idx)
+                .map(|opt| opt.map(|f| GeometryType::from(f))),
             "GEOMETRYCOLLECTION" => row
                 .get_opt::<_, GeometryCollection>(idx)
                 .map(|opt| opt.map(|f| GeometryType::GeometryCollection(f))),
@@ -46,10 +87,36 @@ impl GeometryType {
             }
         };
         // Option<Result<GeometryType, _>> --> Result<GeometryType, String>
-        field.map_or_else(
+        let geom = field.map_or_else(
             || Err("Column not found".to_string()),
             |res| res.map_err(|err| format!("{}", err)),
-        )
+        );
+        match geom {
+            Ok(GeometryType::GeometryCollection(_)) if type_name != "GEOMETRYCOLLECTION" => Err(
+                format!("Unexpected GEOMETRYCOLLECTION in {} field", type_name),
+            ),
+            _ => geom,
+        }
+    }
+    /// Convert geometry of a mixed `GEOMETRY` field based on the `ST_GeometryType` of the row
+    ///
+    /// Clipping may return geometry collections, which are reduced to the geometry kind of the source.
+    pub fn from_mixed_geom_field(
+        row: &Row,
+        idx: &str,
+        st_geometry_type: &str,
+    ) -> Result<GeometryType, String> {
+        let dim = match st_geometry_type {
+            "ST_Point" | "ST_MultiPoint" => 0,
+            "ST_LineString" | "ST_MultiLineString" => 1,
+            "ST_Polygon" | "ST_MultiPolygon" => 2,
+            _ => return Err(format!("Unsupported geometry type {}", st_geometry_type)),
+        };
+        let geom = row.get_opt::<_, ewkb::Geometry>(idx).map_or_else(
+            || Err("Column not found".to_string()),
+            |res| res.map_err(|err| format!("{}", err)),
+        )?;
+        Ok(GeometryType::from(geom).collection_extract(dim))
     }
 }
 
@@ -65,8 +132,19 @@ impl FromSql for FeatureAttrValType {
             | &types::INT2
             | &types::INT4
             | &types::INT8
-            | &types::BOOL => true,
-            _ => false,
+            | &types::BOOL
+            | &types::DATE
+            | &types::TIMESTAMP
+            | &types::TIMESTAMPTZ
+            | &types::UUID
+            | &types::JSON
+            | &types::JSONB => true,
+            _ => match ty.kind() {
+                Kind::Array(member) => FeatureAttrValType::accepts(member),
+                // Enum labels (pg_type.typtype = 'e') are transmitted as text
+                Kind::Enum(_) => true,
+                _ => false,
+            },
         }
     }
     fn from_sql(ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
@@ -90,11 +168,149 @@ impl FromSql for FeatureAttrValType {
             }
             &types::INT8 => <i64>::from_sql(ty, raw).and_then(|v| Ok(FeatureAttrValType::Int(v))),
             &types::BOOL => <bool>::from_sql(ty, raw).and_then(|v| Ok(FeatureAttrValType::Bool(v))),
-            _ => {
-                let err: Box<dyn std::error::Error + Sync + Send> =
-                    format!("cannot convert {} to FeatureAttrValType", ty).into();
-                Err(err)
+            &types::DATE => {
+                date_from_sql(raw).and_then(|v| Ok(FeatureAttrValType::Date(iso_date(v))))
+            }
+            &types::TIMESTAMP | &types::TIMESTAMPTZ => timestamp_from_sql(raw).and_then(|v| {
+                match v {
+                    std::i64::MAX | std::i64::MIN => Err("infinite timestamp".into()),
+                    // Microseconds since 2000-01-01
+                    _ => Ok(FeatureAttrValType::Timestamp(
+                        v.div_euclid(1000) + PG_EPOCH_DAYS * 86_400_000,
+                    )),
+                }
+            }),
+            &types::JSON => <String>::from_sql(&types::TEXT, raw)
+                .and_then(|v| Ok(FeatureAttrValType::String(v))),
+            &types::JSONB => match raw.split_first() {
+                // JSONB binary format: version byte followed by JSON text
+                Some((1, json)) => <String>::from_sql(&types::TEXT, json)
+                    .and_then(|v| Ok(FeatureAttrValType::String(v))),
+                _ => Err("unsupported JSONB encoding version".into()),
+            },
+            &types::UUID => {
+                uuid_from_sql(raw).and_then(|v| Ok(FeatureAttrValType::String(uuid_string(&v))))
             }
+            _ => match ty.kind() {
+                Kind::Array(member) => {
+                    array_json(member, raw).and_then(|v| Ok(FeatureAttrValType::String(v)))
+                }
+                Kind::Enum(_) => <String>::from_sql(&types::TEXT, raw)
+                    .and_then(|v| Ok(FeatureAttrValType::String(v))),
+                _ => {
+                    let err: Box<dyn std::error::Error + Sync + Send> =
+                        format!("cannot convert {} to FeatureAttrValType", ty).into();
+                    Err(err)
+                }
+            },
+        }
+    }
+}
+
+/// Encode PostgreSQL array as JSON array string (multi-dimensional arrays are flattened)
+fn array_json(
+    member: &Type,
+    raw: &[u8],
+) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+    let array = array_from_sql(raw)?;
+    let mut values = array.values();
+    let mut elements = Vec::new();
+    while let Some(value) = values.next()? {
+        let element = match value {
+            Some(raw) => match FeatureAttrValType::from_sql(member, raw)? {
+                FeatureAttrValType::String(v) | FeatureAttrValType::Date(v) => json!(v),
+                FeatureAttrValType::Float(v) => json!(v),
+                FeatureAttrValType::Double(v) => json!(v),
+                FeatureAttrValType::Int(v)
+                | FeatureAttrValType::SInt(v)
+                | FeatureAttrValType::Timestamp(v) => json!(v),
+                FeatureAttrValType::UInt(v) => json!(v),
+                FeatureAttrValType::Bool(v) => json!(v),
+                FeatureAttrValType::VarcharArray(v) => json!(v),
+            },
+            None => serde_json::Value::Null,
+        };
+        elements.push(element);
+    }
+    Ok(serde_json::Value::Array(elements).to_string())
+}
+
+/// Convert PostgreSQL date (days since 2000-01-01) to ISO 8601 date string
+fn iso_date(pg_days: i32) -> String {
+    match pg_days {
+        std::i32::MAX => return "infinity".to_string(),
+        std::i32::MIN => return "-infinity".to_string(),
+        _ => {}
+    }
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = pg_days as i64 + PG_EPOCH_DAYS + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Canonical hyphenated UUID representation
+fn uuid_string(bytes: &[u8; 16]) -> String {
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Column is returned as attribute, i.e. it isn't a geometry, geometry type or fid column
+pub(crate) fn is_attribute_column(layer: &Layer, name: &str) -> bool {
+    let geometry = layer.geometry_fields().iter().any(|f| f.as_str() == name);
+    let fid = fid_column(layer) == Some(name);
+    !geometry && !fid && name != GEOMETRY_TYPE_COLUMN
+}
+
+/// Counter of undecodable attribute values, logged as summary instead of once per row
+#[derive(Default)]
+pub(crate) struct FieldErrors {
+    /// Field name -> (type name, number of skipped values)
+    counts: RefCell<BTreeMap<String, (String, u64)>>,
+}
+
+impl FieldErrors {
+    /// Count a skipped value. Returns `true` for the first value of a field.
+    pub fn add(&self, field: &str, type_name: &str) -> bool {
+        let mut counts = self.counts.borrow_mut();
+        let entry = counts
+            .entry(field.to_string())
+            .or_insert((type_name.to_string(), 0));
+        entry.1 += 1;
+        entry.1 == 1
+    }
+    /// Number of skipped values per field
+    pub fn summary(&self, layer_name: &str) -> Vec<String> {
+        self.counts
+            .borrow()
+            .iter()
+            .map(|(field, (type_name, count))| {
+                format!(
+                    "Layer '{}': skipped {} values of field '{}' (type {})",
+                    layer_name, count, field, type_name
+                )
+            })
+            .collect()
+    }
+    pub fn log_summary(&self, layer_name: &str) {
+        for msg in self.summary(layer_name) {
+            warn!("{}", msg);
         }
     }
 }
@@ -102,14 +318,18 @@ impl FromSql for FeatureAttrValType {
 pub(crate) struct FeatureRow<'a> {
     pub layer: &'a Layer,
     pub row: &'a Row<'a>,
+    /// Geometry column (0: geometry_field, 1..: additional_geometry)
+    pub geometry_idx: usize,
+    pub field_errors: &'a FieldErrors,
 }
 
 impl<'a> Feature for FeatureRow<'a> {
     fn fid(&self) -> Option<u64> {
-        self.layer.fid_field.as_ref().and_then(|fid| {
-            let val = self.row.get_opt::<_, FeatureAttrValType>(fid as &str);
+        fid_column(self.layer).and_then(|fid| {
+            let val = self.row.get_opt::<_, FeatureAttrValType>(fid);
             match val {
                 Some(Ok(FeatureAttrValType::Int(fid))) => Some(fid as u64),
+                Some(Ok(FeatureAttrValType::String(fid))) => Some(fid_hash(&fid)),
                 _ => None,
             }
         })
@@ -117,15 +337,8 @@ impl<'a> Feature for FeatureRow<'a> {
     fn attributes(&self) -> Vec<FeatureAttr> {
         let mut attrs = Vec::new();
         for (i, col) in self.row.columns().into_iter().enumerate() {
-            // Skip geometry_field and fid_field
-            if col.name()
-                != self
-                    .layer
-                    .geometry_field
-                    .as_ref()
-                    .unwrap_or(&"".to_string())
-                && col.name() != self.layer.fid_field.as_ref().unwrap_or(&"".to_string())
-            {
+            // Geometry and fid columns are never renamed by field aliases
+            if is_attribute_column(self.layer, col.name()) {
                 let val = self.row.get_opt::<_, Option<FeatureAttrValType>>(i);
                 match val.unwrap() {
                     Ok(Some(v)) => {
@@ -139,13 +352,15 @@ impl<'a> Feature for FeatureRow<'a> {
                         // Skip NULL values
                     }
                     Err(err) => {
-                        warn!(
-                            "Layer '{}' - skipping field '{}': {}",
-                            self.layer.name,
-                            col.name(),
-                            err
-                        );
-                        //warn!("{:?}", self.row);
+                        // Details of first error only, followed by a summary
+                        if self.field_errors.add(col.name(), col.type_().name()) {
+                            warn!(
+                                "Layer '{}' - skipping field '{}': {}",
+                                self.layer.name,
+                                col.name(),
+                                err
+                            );
+                        }
                     }
                 }
             }
@@ -153,19 +368,7 @@ impl<'a> Feature for FeatureRow<'a> {
         attrs
     }
     fn geometry(&self) -> Result<GeometryType, String> {
-        let geom = GeometryType::from_geom_field(
-            &self.row,
-            &self
-                .layer
-                .geometry_field
-                .as_ref()
-                .expect("geometry_field undefined"),
-            &self
-                .layer
-                .geometry_type
-                .as_ref()
-                .expect("geometry_type undefined"),
-        );
+        let geom = self.decode_geometry();
         if let Err(ref err) = geom {
             error!("Layer '{}': {}", self.layer.name, err);
             error!("{:?}", self.row);
@@ -173,3 +376,53 @@ impl<'a> Feature for FeatureRow<'a> {
         geom
     }
 }
+
+impl<'a> FeatureRow<'a> {
+    /// Geometry column has an empty geometry (e.g. clipped or simplified away)
+    pub fn has_empty_geometry(&self) -> bool {
+        self.decode_geometry().map_or(false, |geom| geom.is_empty())
+    }
+    fn decode_geometry(&self) -> Result<GeometryType, String> {
+        let (geometry_field, geometry_type) = if self.geometry_idx == 0 {
+            let geometry_field = self.layer.geometry_field.as_ref().ok_or(format!(
+                "Layer '{}': geometry_field undefined",
+                self.layer.name
+            ))?;
+            (geometry_field, self.layer.geometry_type.as_ref())
+        } else {
+            let geom = &self.layer.additional_geometry[self.geometry_idx - 1];
+            (&geom.geometry_field, geom.geometry_type.as_ref())
+        };
+        let geom_type = base_geometry_type(geometry_type.ok_or(format!(
+            "Layer '{}': geometry_type of '{}' undefined",
+            self.layer.name, geometry_field
+        ))?);
+        // Only points keep their Z ordinate, other types are queried in 2D
+        let type_name =
+            if geom_type == "POINT" && self.layer.geometry_dimensions(geometry_type) == 3 {
+                "POINTZ"
+            } else {
+                geom_type
+            };
+        // Mixed geometry types are dispatched by the type of each row
+        let st_geometry_type = if type_name == "GEOMETRY" && self.geometry_idx == 0 {
+            match self.row.get_opt::<_, Option<String>>(GEOMETRY_TYPE_COLUMN) {
+                Some(Ok(st_type)) => st_type,
+                _ => None,
+            }
+        } else {
+            None
+        };
+        match st_geometry_type {
+            Some(ref st_type) => {
+                GeometryType::from_mixed_geom_field(&self.row, geometry_field, st_type)
+            }
+            None => GeometryType::from_geom_field(
+                &self.row,
+                geometry_field,
+                type_name,
+                self.layer.force_multi,
+            ),
+        }
+    }
+}
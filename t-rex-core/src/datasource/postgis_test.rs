@@ -3,11 +3,14 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
+use crate::core::config::Config;
 use crate::core::feature::FeatureAttrValType;
 use crate::core::geom::*;
-use crate::core::layer::{Layer, LayerQuery};
+use crate::core::layer::{
+    BufferUnits, GroupMerge, Layer, LayerField, LayerQuery, LimitMode, ParamValue,
+};
 use crate::datasource::postgis_ds::{PostgisDatasource, QueryParam};
-use crate::datasource::DatasourceType;
+use crate::datasource::{DatasourceError, DatasourceType, FeatureBudget};
 use postgres;
 use postgres::Connection;
 use std::env;
@@ -29,7 +32,7 @@ fn test_from_geom_fields() {
             &*format!("{:?}", geom),
             "Point { x: -6438719.622820721, y: -4093437.7144101723, srid: Some(3857) }"
         );
-        let geom = GeometryType::from_geom_field(&row, "wkb_geometry", "POINT");
+        let geom = GeometryType::from_geom_field(&row, "wkb_geometry", "POINT", true);
         assert_eq!(
             &*format!("{:?}", geom),
             "Ok(Point(Point { x: -6438719.622820721, y: -4093437.7144101723, srid: Some(3857) }))"
@@ -38,7 +41,7 @@ fn test_from_geom_fields() {
 
     let sql = "SELECT ST_Multi(wkb_geometry) AS wkb_geometry FROM ne.rivers_lake_centerlines WHERE name='Waiau' AND ST_NPoints(wkb_geometry)<10";
     for row in &conn.query(sql, &[]).unwrap() {
-        let geom = GeometryType::from_geom_field(&row, "wkb_geometry", "LINESTRING");
+        let geom = GeometryType::from_geom_field(&row, "wkb_geometry", "LINESTRING", true);
         assert_eq!(&*format!("{:?}", geom),
                    "Ok(MultiLineString(MultiLineStringT { lines: [LineStringT { points: [Point { x: 18672061.098933436, y: -5690573.725394946, srid: None }, Point { x: 18671798.382036217, y: -5692123.11701991, srid: None }, Point { x: 18671707.790002696, y: -5693530.713572942, srid: None }, Point { x: 18671789.322832868, y: -5694822.281317252, srid: None }, Point { x: 18672061.098933436, y: -5695997.770001522, srid: None }, Point { x: 18670620.68560042, y: -5698245.837796968, srid: None }, Point { x: 18668283.41113552, y: -5700403.997584983, srid: None }, Point { x: 18666082.024720907, y: -5701179.511527114, srid: None }, Point { x: 18665148.926775623, y: -5699253.775757339, srid: None }], srid: None }], srid: Some(3857) }))");
     }
@@ -57,6 +60,39 @@ fn test_from_geom_fields() {
     assert_eq!(format!("{}", rows.columns()[1].type_()), "bytea");
 }
 
+#[test]
+#[ignore]
+fn test_describe_layers() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    pg.conn()
+        .unwrap()
+        .batch_execute("ANALYZE ne.ne_10m_rivers_lake_centerlines")
+        .unwrap();
+    let infos = pg.describe_layers();
+    assert_eq!(infos.len(), pg.detect_layers(false).len());
+    let rivers = infos
+        .iter()
+        .find(|info| info.table == "ne_10m_rivers_lake_centerlines")
+        .unwrap();
+    assert_eq!(rivers.schema, "ne");
+    assert_eq!(rivers.geometry_column, "wkb_geometry");
+    assert_eq!(rivers.srid, 3857);
+    assert_eq!(rivers.geometry_types, vec!["MULTILINESTRING".to_string()]);
+    assert!(rivers.row_estimate.unwrap() > 0);
+    assert_eq!(rivers.spatial_index, Some(true));
+
+    // Types of untyped geometry columns are detected
+    let mixed = infos
+        .iter()
+        .find(|info| info.table == "mixed_geometries")
+        .unwrap();
+    assert!(mixed.geometry_types.len() > 1);
+}
+
 #[test]
 #[ignore]
 fn test_detect_layers() {
@@ -69,6 +105,84 @@ fn test_detect_layers() {
     assert!(layers
         .iter()
         .any(|ref layer| layer.name == "rivers_lake_centerlines"));
+
+    // Parallel geometry type detection keeps the layer order
+    let pg: PostgisDatasource =
+        PostgisDatasource::new(&env::var("DBCONN").unwrap(), Some(4)).connected();
+    let detected = pg.detect_layers(true);
+    let names = |layers: &Vec<Layer>| layers.iter().map(|l| l.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&detected), names(&layers));
+    let mixed = detected
+        .iter()
+        .find(|layer| layer.name == "mixed_geometries")
+        .unwrap();
+    assert_eq!(mixed.geometry_type, Some("GEOMETRY".to_string()));
+
+    // Sorted by schema and table name
+    let tables: Vec<(String, String)> = layers
+        .iter()
+        .map(|layer| {
+            let table_name = layer.table_name.as_ref().unwrap().replace('"', "");
+            match table_name.split_once('.') {
+                Some((schema, table)) => (schema.to_string(), table.to_string()),
+                None => ("public".to_string(), table_name.clone()),
+            }
+        })
+        .collect();
+    let mut sorted = tables.clone();
+    sorted.sort();
+    assert_eq!(tables, sorted);
+}
+
+#[test]
+fn test_detect_layers_sql() {
+    use crate::datasource::postgis_ds::glob_to_like;
+
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    assert_eq!(
+        pg.detect_layers_sql(),
+        "SELECT * FROM geometry_columns ORDER BY f_table_schema COLLATE \"C\",f_table_name COLLATE \"C\""
+    );
+    pg.include_schemas = vec!["tiles".to_string()];
+    pg.table_filter = Some("osm_*".to_string());
+    assert_eq!(
+        pg.detect_layers_sql(),
+        "SELECT * FROM geometry_columns WHERE f_table_schema::text = ANY($1) AND f_table_name::text LIKE $2 ORDER BY f_table_schema COLLATE \"C\",f_table_name COLLATE \"C\""
+    );
+    pg.exclude_schemas = vec!["topology".to_string()];
+    pg.table_filter = None;
+    assert_eq!(
+        pg.detect_layers_sql(),
+        "SELECT * FROM geometry_columns WHERE f_table_schema::text = ANY($1) AND f_table_schema::text <> ALL($2) ORDER BY f_table_schema COLLATE \"C\",f_table_name COLLATE \"C\""
+    );
+
+    assert_eq!(glob_to_like("osm_*"), "osm\\_%");
+    assert_eq!(glob_to_like("ne_10m_?"), "ne\\_10m\\__");
+    assert_eq!(glob_to_like("100%"), "100\\%");
+}
+
+#[test]
+#[ignore]
+fn test_detect_geometry_types() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    let mut layer = Layer::new("mixed");
+    layer.table_name = Some(String::from("ne.mixed_geometries"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    // Points come first in the table
+    pg.detect_limit = 1;
+    assert_eq!(
+        pg.detect_geometry_types(&layer),
+        Ok(vec!["POINT".to_string()])
+    );
+    pg.detect_limit = 0;
+    assert_eq!(pg.detect_geometry_types(&layer).unwrap().len(), 2);
+
+    layer.table_name = Some(String::from("ne.missing_table"));
+    assert!(pg.detect_geometry_types(&layer).is_err());
 }
 
 #[test]
@@ -97,13 +211,89 @@ fn test_detect_columns() {
 
 #[test]
 #[ignore]
-fn test_extent_query() {
+fn test_fields_query() {
     let pg: PostgisDatasource = match env::var("DBCONN") {
         Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
         Result::Err(_) => panic!("DBCONN undefined"),
     }
     .unwrap();
     let layers = pg.detect_layers(false);
+    let mut layer = layers
+        .iter()
+        .find(|ref layer| layer.name == "rivers_lake_centerlines")
+        .unwrap()
+        .clone();
+    layer.fields = vec![LayerField::new("name"), LayerField::new("unknown")];
+    let sql = pg.build_query(&layer, 3857, 10, None).unwrap().sql;
+    assert!(sql.contains(",\"name\" FROM "));
+    assert!(!sql.contains("scalerank"));
+    assert!(!sql.contains("fid"));
+
+    layer.fid_field = Some("fid".to_string());
+    let sql = pg.build_query(&layer, 3857, 10, None).unwrap().sql;
+    assert!(sql.contains(",\"fid\",\"name\" FROM "));
+
+    layer.fields = Vec::new();
+    layer.exclude_fields = vec!["name".to_string()];
+    let sql = pg.build_query(&layer, 3857, 10, None).unwrap().sql;
+    assert!(sql.contains(",\"fid\",\"scalerank\" FROM "));
+
+    layer.exclude_fields = Vec::new();
+    layer.fields = vec![LayerField {
+        name: "scalerank".to_string(),
+        alias: Some("rank".to_string()),
+    }];
+    let sql = pg.build_query(&layer, 3857, 10, None).unwrap().sql;
+    assert!(sql.contains(",\"fid\",\"scalerank\" AS \"rank\" FROM "));
+}
+
+#[test]
+#[ignore]
+fn test_retrieve_renamed_fields() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.fid_field = Some(String::from("fid"));
+    layer.fields = vec![LayerField {
+        name: "pop_max".to_string(),
+        alias: Some("pop".to_string()),
+    }];
+    let grid = Grid::web_mercator();
+    let extent = Extent {
+        minx: 821850.9,
+        miny: 5909499.5,
+        maxx: 860986.7,
+        maxy: 5948635.3,
+    };
+    pg.prepare_queries("ts", &layer, &grid);
+    let mut reccnt = 0;
+    pg.retrieve_features("ts", &layer, &extent, 10, &grid, None, |feat| {
+        assert_eq!(feat.attributes().len(), 1);
+        assert_eq!(feat.attributes()[0].key, "pop");
+        assert!(feat.fid().is_some());
+        reccnt += 1;
+    })
+    .unwrap();
+    assert_eq!(1, reccnt);
+}
+
+#[test]
+#[ignore]
+fn test_extent_query() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    pg.exact_extent = true;
+    let layers = pg.detect_layers(false);
     let layer = &layers
         .iter()
         .find(|ref layer| layer.name == "rivers_lake_centerlines")
@@ -117,189 +307,1777 @@ fn test_extent_query() {
             maxy: 75.79348379113983,
         })
     );
+    let wkt = pg.raw_extent_wkt(&layer).unwrap();
+    assert!(wkt.starts_with("POLYGON(("));
+
+    let mut layer = Layer::new("empty");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places WHERE false"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.srid = Some(3857);
+    assert_eq!(pg.layer_extent(&layer, 3857), None);
+    assert_eq!(pg.raw_extent_wkt(&layer), None);
+
+    // Query without rows
+    layer.table_name = Some(String::from(
+        "ne.ne_10m_populated_places GROUP BY featurecla HAVING false",
+    ));
+    assert_eq!(pg.layer_extent(&layer, 3857), None);
+
+    layer.geometry_field = None;
+    assert_eq!(pg.layer_extent(&layer, 3857), None);
 }
 
 #[test]
-fn test_feature_query() {
-    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
-    let mut layer = Layer::new("points");
-    layer.table_name = Some(String::from("osm_place_point"));
-    layer.geometry_field = Some(String::from("geometry"));
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+fn test_split_table_name() {
+    use crate::datasource::postgis_ds::split_table_name;
 
-    // reprojection
-    layer.srid = Some(2056);
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),2056)");
-    layer.no_transform = true;
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,2056)");
-    layer.no_transform = false;
-    layer.srid = Some(4326);
     assert_eq!(
-        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-        "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326)"
+        split_table_name("osm_place_point"),
+        Some((None, "osm_place_point".to_string()))
     );
-    layer.shift_longitude = true;
     assert_eq!(
-        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-        "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Shift_Longitude(ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326))"
+        split_table_name("ne.Rivers"),
+        Some((Some("ne".to_string()), "rivers".to_string()))
     );
-    layer.shift_longitude = false;
-    layer.srid = Some(-1);
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,-1)");
-    layer.srid = Some(3857);
     assert_eq!(
-        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+        split_table_name(r#""NE"."Rivers ""Lakes""""#),
+        Some((Some("NE".to_string()), r#"Rivers "Lakes""#.to_string()))
     );
+    assert_eq!(split_table_name("ne.rivers WHERE false"), None);
+    assert_eq!(split_table_name("(SELECT * FROM rivers) AS r"), None);
+    assert_eq!(split_table_name("db.ne.rivers"), None);
+}
 
-    // clipping
-    layer.buffer_size = Some(10);
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_Intersection(geometry,ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
-    layer.make_valid = true;
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_Intersection(ST_MakeValid(geometry),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
-    layer.geometry_type = Some("POLYGON".to_string());
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_Multi(ST_Buffer(ST_Intersection(ST_MakeValid(geometry),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)), 0.0)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
-    layer.geometry_type = Some("POINT".to_string());
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
-    layer.buffer_size = Some(0);
-    assert_eq!(
-        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
-    );
+#[test]
+fn test_layer_extent_query() {
+    use crate::datasource::postgis_ds::layer_extent_query;
 
-    layer.buffer_size = None;
-    layer.geometry_type = Some("POLYGON".to_string());
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    assert_eq!(layer_extent_query(&layer, 3857), None);
 
-    // simplification
-    layer.simplify = true;
-    layer.tolerance = "!pixel_width!/2".to_string();
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT COALESCE(ST_MakeValid(ST_SnapToGrid(ST_Multi(geometry), $5::FLOAT8/2)),ST_GeomFromText(\'MULTIPOLYGON EMPTY\',3857))::geometry(MULTIPOLYGON,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
-    layer.geometry_type = Some("LINESTRING".to_string());
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_Multi(ST_SimplifyPreserveTopology(ST_Multi(geometry),$5::FLOAT8/2)) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
-    layer.tolerance = "0.5".to_string();
-    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-               "SELECT ST_Multi(ST_SimplifyPreserveTopology(ST_Multi(geometry),0.5)) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
-    layer.geometry_type = Some("POINT".to_string());
+    layer.srid = Some(2056);
     assert_eq!(
-        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+        layer_extent_query(&layer, 3857),
+        Some("SELECT ST_Transform(ST_SetSRID(ST_Extent(wkb_geometry),2056),4326) AS extent FROM ne.ne_10m_populated_places".to_string())
+    );
+    // Layer coordinates are grid coordinates
+    layer.no_transform = true;
+    assert_eq!(
+        layer_extent_query(&layer, 3857),
+        Some("SELECT ST_Transform(ST_SetSRID(ST_Extent(wkb_geometry),3857),4326) AS extent FROM ne.ne_10m_populated_places".to_string())
     );
 
-    layer.simplify = false;
-    layer.query_limit = Some(1);
+    layer.no_transform = false;
+    layer.srid = Some(0);
+    layer.assume_srid = Some(21781);
     assert_eq!(
-        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
-        // No LIMIT clause added - limited when retrieving records
-        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+        layer_extent_query(&layer, 3857),
+        Some("SELECT ST_Transform(ST_SetSRID(ST_Extent(wkb_geometry),21781),4326) AS extent FROM ne.ne_10m_populated_places".to_string())
     );
+}
 
-    // user queries
-    layer.query = vec![LayerQuery {
-        minzoom: 0,
-        maxzoom: Some(22),
-        simplify: None,
-        tolerance: None,
-        sql: Some(String::from("SELECT geometry AS geom FROM osm_place_point")),
-    }];
-    layer.query_limit = None;
-    assert_eq!(pg.build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
-                   .unwrap()
-                   .sql,
-               "SELECT * FROM (SELECT geometry AS geom FROM osm_place_point) AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+#[test]
+#[ignore]
+fn test_estimated_extent() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    pg.conn()
+        .unwrap()
+        .batch_execute("ANALYZE ne.ne_10m_rivers_lake_centerlines")
+        .unwrap();
+    let mut layer = Layer::new("rivers_lake_centerlines");
+    layer.table_name = Some(String::from("ne.ne_10m_rivers_lake_centerlines"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.srid = Some(3857);
+    let estimated = pg.layer_extent(&layer, 3857).unwrap();
+    // Approximately the exact extent
+    assert!((estimated.minx - -164.90347246002037).abs() < 1.0);
+    assert!((estimated.maxy - 75.79348379113983).abs() < 1.0);
 
-    layer.query = vec![LayerQuery {
-        minzoom: 0,
-        maxzoom: Some(22),
-        simplify: None,
-        tolerance: None,
-        sql: Some(String::from(
-            "SELECT * FROM osm_place_point WHERE name='Bern'",
-        )),
-    }];
-    assert_eq!(pg.build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
-                   .unwrap()
-                   .sql,
-               "SELECT * FROM (SELECT * FROM osm_place_point WHERE name='Bern') AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    // Cached per layer
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    assert_eq!(pg.layer_extent(&layer, 3857), Some(estimated));
 
-    // out of maxzoom
-    //assert_eq!(pg.query(&layer, 23).unwrap().sql,
-    //    "SELECT * FROM (SELECT geometry FROM osm_place_point) AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
-    //layer.table_name = None;
-    //assert!(pg.query(&layer, 23).is_none());
+    // Fallback to ST_Extent for tables without statistics
+    pg.conn()
+        .unwrap()
+        .batch_execute("CREATE TEMP TABLE rivers_no_stats AS SELECT wkb_geometry FROM ne.ne_10m_rivers_lake_centerlines")
+        .unwrap();
+    let mut layer = Layer::new("rivers_no_stats");
+    layer.table_name = Some(String::from("rivers_no_stats"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.srid = Some(3857);
+    assert_eq!(
+        pg.layer_extent(&layer, 3857),
+        Some(Extent {
+            minx: -164.90347246002037,
+            miny: -52.1577287739643,
+            maxx: 177.2111922535212,
+            maxy: 75.79348379113983,
+        })
+    );
 }
 
 #[test]
-fn test_query_params() {
-    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
-    let mut layer = Layer::new("buildings");
-    layer.geometry_field = Some(String::from("way"));
+#[ignore]
+fn test_no_transform_extent() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    pg.exact_extent = true;
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    // Web Mercator coordinates labeled with a different SRID
+    layer.srid = Some(2056);
+    layer.no_transform = true;
+    let extent = pg.layer_extent(&layer, 3857).unwrap();
+    let grid_extent = pg.extent_from_wgs84(&extent, 3857).unwrap();
 
-    layer.query = vec![LayerQuery {
-                           minzoom: 0,
-                           maxzoom: Some(22),
-                           simplify: None,
-                           tolerance: None,
-                           sql: Some(String::from("SELECT name, type, 0 as osm_id, ST_Union(geometry) AS way FROM osm_buildings_gen0 WHERE geometry && !bbox!")),
-                       }];
-    let query = pg
-        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+    let conn = pg.conn().unwrap();
+    let rows = conn
+        .query(
+            "SELECT ST_XMin(e), ST_YMin(e), ST_XMax(e), ST_YMax(e) FROM (SELECT ST_Extent(wkb_geometry) AS e FROM ne.ne_10m_populated_places) AS _e",
+            &[],
+        )
         .unwrap();
-    assert_eq!(query.sql,
-               "SELECT * FROM (SELECT name, type, 0 as osm_id, ST_Union(geometry) AS way FROM osm_buildings_gen0 WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _q");
-    assert_eq!(query.params, [QueryParam::Bbox]);
+    let row = rows.get(0);
+    let raw: Vec<f64> = (0..4).map(|i| row.get(i)).collect();
+    let projected = [
+        grid_extent.minx,
+        grid_extent.miny,
+        grid_extent.maxx,
+        grid_extent.maxy,
+    ];
+    for (raw, projected) in raw.iter().zip(projected.iter()) {
+        assert!((raw - projected).abs() < 0.01);
+    }
+}
 
-    layer.query = vec![LayerQuery {
-                           minzoom: 0,
-                           maxzoom: Some(22),
-                           simplify: None,
-                           tolerance: None,
-                           sql: Some(String::from("SELECT osm_id, geometry, typen FROM landuse_z13toz14n WHERE !zoom! BETWEEN 13 AND 14) AS landuse_z9toz14n")),
-                       }];
-    let query = pg
-        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+#[test]
+#[ignore]
+fn test_feature_row_geometry() {
+    use crate::core::feature::Feature;
+    use crate::datasource::postgis_fields::{FeatureRow, FieldErrors};
+
+    let conn: Connection = match env::var("DBCONN") {
+        Result::Ok(val) => Connection::connect(&val as &str, postgres::TlsMode::None),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    let sql = "SELECT wkb_geometry FROM ne.ne_10m_populated_places LIMIT 1";
+    let field_errors = FieldErrors::default();
+    let mut layer = Layer::new("points");
+    for row in &conn.query(sql, &[]).unwrap() {
+        let feature = FeatureRow {
+            layer: &layer,
+            row: &row,
+            geometry_idx: 0,
+            field_errors: &field_errors,
+        };
+        assert_eq!(
+            feature.geometry().err(),
+            Some("Layer 'points': geometry_field undefined".to_string())
+        );
+    }
+
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = None;
+    for row in &conn.query(sql, &[]).unwrap() {
+        let feature = FeatureRow {
+            layer: &layer,
+            row: &row,
+            geometry_idx: 0,
+            field_errors: &field_errors,
+        };
+        assert_eq!(
+            feature.geometry().err(),
+            Some("Layer 'points': geometry_type of 'wkb_geometry' undefined".to_string())
+        );
+    }
+
+    layer.geometry_type = Some(String::from("POINT"));
+    for row in &conn.query(sql, &[]).unwrap() {
+        let feature = FeatureRow {
+            layer: &layer,
+            row: &row,
+            geometry_idx: 0,
+            field_errors: &field_errors,
+        };
+        assert!(feature.geometry().is_ok());
+    }
+}
+
+#[test]
+fn test_attribute_columns() {
+    use crate::datasource::postgis_fields::is_attribute_column;
+
+    let mut layer = Layer::new("points");
+    // Empty column names were treated as fid_field without fid_field
+    assert!(is_attribute_column(&layer, ""));
+    assert!(is_attribute_column(&layer, "name"));
+    assert!(!is_attribute_column(&layer, "_geometry_type"));
+
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.fid_field = Some(String::from("fid"));
+    assert!(is_attribute_column(&layer, ""));
+    assert!(!is_attribute_column(&layer, "wkb_geometry"));
+    assert!(!is_attribute_column(&layer, "fid"));
+    assert!(is_attribute_column(&layer, "fid2"));
+}
+
+#[test]
+fn test_field_errors() {
+    use crate::datasource::postgis_fields::FieldErrors;
+
+    let errors = FieldErrors::default();
+    assert!(errors.add("tags", "hstore"));
+    assert!(!errors.add("tags", "hstore"));
+    assert!(errors.add("range", "int4range"));
+    assert_eq!(
+        errors.summary("osm"),
+        vec![
+            "Layer 'osm': skipped 1 values of field 'range' (type int4range)",
+            "Layer 'osm': skipped 2 values of field 'tags' (type hstore)"
+        ]
+    );
+}
+
+#[test]
+fn test_attr_from_sql() {
+    use postgres::types::{self, FromSql};
+
+    // 2020-06-25 = 7481 days since 2000-01-01
+    let val = FeatureAttrValType::from_sql(&types::DATE, &7481i32.to_be_bytes()).unwrap();
+    assert_eq!(val, FeatureAttrValType::Date("2020-06-25".to_string()));
+    let val = FeatureAttrValType::from_sql(&types::DATE, &(-1i32).to_be_bytes()).unwrap();
+    assert_eq!(val, FeatureAttrValType::Date("1999-12-31".to_string()));
+
+    // 2020-06-25 12:00:00.5 UTC
+    let micros: i64 = (7481 * 86_400 + 12 * 3600) * 1_000_000 + 500_000;
+    let val = FeatureAttrValType::from_sql(&types::TIMESTAMPTZ, &micros.to_be_bytes()).unwrap();
+    assert_eq!(val, FeatureAttrValType::Timestamp(1_593_086_400_500));
+    let val = FeatureAttrValType::from_sql(&types::TIMESTAMP, &(-1i64).to_be_bytes()).unwrap();
+    assert_eq!(val, FeatureAttrValType::Timestamp(946_684_799_999));
+
+    let uuid = [
+        0x55, 0x0e, 0x84, 0x00, 0xe2, 0x9b, 0x41, 0xd4, 0xa7, 0x16, 0x44, 0x66, 0x55, 0x44, 0x00,
+        0x00,
+    ];
+    let val = FeatureAttrValType::from_sql(&types::UUID, &uuid).unwrap();
+    assert_eq!(
+        val,
+        FeatureAttrValType::String("550e8400-e29b-41d4-a716-446655440000".to_string())
+    );
+
+    let json = br#"{"name": "Bern", "tags": [1, 2]}"#;
+    let val = FeatureAttrValType::from_sql(&types::JSON, json).unwrap();
+    assert_eq!(
+        val,
+        FeatureAttrValType::String(r#"{"name": "Bern", "tags": [1, 2]}"#.to_string())
+    );
+    let jsonb = [&[1u8], &json[..]].concat();
+    let val = FeatureAttrValType::from_sql(&types::JSONB, &jsonb).unwrap();
+    assert_eq!(
+        val,
+        FeatureAttrValType::String(r#"{"name": "Bern", "tags": [1, 2]}"#.to_string())
+    );
+
+    // int4[] '{1,NULL,-3}': ndim, has_nulls, element oid, dimension size, lower bound, elements
+    let mut int4_array = vec![0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 23, 0, 0, 0, 3, 0, 0, 0, 1];
+    int4_array.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 1]);
+    int4_array.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    int4_array.extend_from_slice(&[0, 0, 0, 4, 0xff, 0xff, 0xff, 0xfd]);
+    assert!(FeatureAttrValType::accepts(&types::INT4_ARRAY));
+    let val = FeatureAttrValType::from_sql(&types::INT4_ARRAY, &int4_array).unwrap();
+    assert_eq!(val, FeatureAttrValType::String("[1,null,-3]".to_string()));
+
+    // text[] '{a,"b\"c"}'
+    let mut text_array = vec![0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 25, 0, 0, 0, 2, 0, 0, 0, 1];
+    text_array.extend_from_slice(&[0, 0, 0, 1, b'a']);
+    text_array.extend_from_slice(&[0, 0, 0, 3, b'b', b'"', b'c']);
+    let val = FeatureAttrValType::from_sql(&types::TEXT_ARRAY, &text_array).unwrap();
+    assert_eq!(
+        val,
+        FeatureAttrValType::String(r#"["a","b\"c"]"#.to_string())
+    );
+
+    // CREATE TYPE mood AS ENUM ('sad', 'happy')
+    let mood = types::Type::_new(
+        "mood".to_string(),
+        100_000,
+        types::Kind::Enum(vec!["sad".to_string(), "happy".to_string()]),
+        "public".to_string(),
+    );
+    assert!(FeatureAttrValType::accepts(&mood));
+    let val = FeatureAttrValType::from_sql(&mood, b"happy").unwrap();
+    assert_eq!(val, FeatureAttrValType::String("happy".to_string()));
+}
+
+#[test]
+#[ignore]
+fn test_detection_cache() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    pg.conn()
+        .unwrap()
+        .batch_execute("CREATE TEMP TABLE cache_test AS SELECT 1 AS a")
         .unwrap();
-    assert_eq!(query.sql,
-               "SELECT * FROM (SELECT osm_id, geometry, typen FROM landuse_z13toz14n WHERE $5 BETWEEN 13 AND 14) AS landuse_z9toz14n) AS _q WHERE way && ST_MakeEnvelope($1,$2,$3,$4,3857)");
-    assert_eq!(query.params, [QueryParam::Bbox, QueryParam::Zoom]);
+    let layer = Layer::new("cache_test");
+    let sql = String::from("SELECT * FROM cache_test");
+    let cols = vec![("a".to_string(), "".to_string())];
+    assert_eq!(pg.detect_columns(&layer, Some(&sql)), cols);
+
+    pg.conn()
+        .unwrap()
+        .batch_execute("ALTER TABLE cache_test ADD COLUMN b TEXT")
+        .unwrap();
+    // Cached for the same query text
+    let sql2 = String::from("  SELECT * FROM cache_test\n");
+    assert_eq!(pg.detect_columns(&layer, Some(&sql2)), cols);
+    // Whitespace within the query may be significant (e.g. in literals)
+    let sql3 = String::from("SELECT *  FROM cache_test");
+    assert_eq!(pg.detect_columns(&layer, Some(&sql3)).len(), 2);
 
+    pg.clear_detection_cache();
+    assert_eq!(pg.detect_columns(&layer, Some(&sql)).len(), 2);
+}
+
+#[test]
+#[ignore]
+fn test_enum_columns() {
+    use crate::core::feature::FeatureAttr;
+
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    // Temporary type of the single pooled connection
+    pg.conn()
+        .unwrap()
+        .batch_execute("CREATE TYPE pg_temp.t_rex_mood AS ENUM ('sad', 'happy')")
+        .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.query_limit = Some(1);
+    let sql = String::from(
+        "SELECT wkb_geometry, 'happy'::pg_temp.t_rex_mood AS mood FROM ne.ne_10m_populated_places",
+    );
+    assert_eq!(
+        pg.detect_columns(&layer, Some(&sql)),
+        vec![
+            ("wkb_geometry".to_string(), "".to_string()),
+            ("mood".to_string(), "".to_string())
+        ]
+    );
     layer.query = vec![LayerQuery {
-                           minzoom: 0,
-                           maxzoom: Some(22),
-                           simplify: None,
-                           tolerance: None,
-                           sql: Some(String::from("SELECT name, type, 0 as osm_id, ST_SimplifyPreserveTopology(ST_Union(geometry),!pixel_width!/2) AS way FROM osm_buildings")),
-                       }];
-    let query = pg
-        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(sql),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(0, 0, 0);
+    pg.prepare_queries("ts", &layer, &grid);
+    let mut attrs: Vec<FeatureAttr> = Vec::new();
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, None, |feat| {
+        attrs = feat.attributes();
+    });
+    assert_eq!(result, Ok(1));
+    assert_eq!(attrs[0].key, "mood");
+    assert_eq!(
+        attrs[0].value,
+        FeatureAttrValType::String("happy".to_string())
+    );
+}
+
+#[test]
+fn test_fid_hash() {
+    use crate::core::feature::fid_hash;
+
+    // FNV-1a test vectors
+    assert_eq!(fid_hash(""), 0xcbf29ce484222325);
+    assert_eq!(fid_hash("a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(
+        fid_hash("550e8400-e29b-41d4-a716-446655440000"),
+        fid_hash("550e8400-e29b-41d4-a716-446655440000")
+    );
+    assert_ne!(
+        fid_hash("550e8400-e29b-41d4-a716-446655440000"),
+        fid_hash("550e8400-e29b-41d4-a716-446655440001")
+    );
+}
+
+#[test]
+fn test_connection_timeout() {
+    use std::time::{Duration, Instant};
+
+    // Non-routable address
+    let mut pg = PostgisDatasource::new("postgresql://t_rex@10.255.255.1/t_rex", Some(1));
+    pg.connection_timeout = Some(1);
+    let now = Instant::now();
+    let err = pg.try_connected().err().unwrap();
+    assert!(err.starts_with("Couldn't connect to 'postgresql://t_rex@10.255.255.1/t_rex': "));
+    // Initial connection attempt and TLS retry
+    assert!(now.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_connect_retries() {
+    use crate::datasource::postgis_ds::retry_with_backoff;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    // Fake endpoint accepting connections after a startup delay
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
         .unwrap();
-    assert_eq!(query.sql,
-               "SELECT * FROM (SELECT name, type, 0 as osm_id, ST_SimplifyPreserveTopology(ST_Union(geometry),$5::FLOAT8/2) AS way FROM osm_buildings) AS _q WHERE way && ST_MakeEnvelope($1,$2,$3,$4,3857)");
-    assert_eq!(query.params, [QueryParam::Bbox, QueryParam::PixelWidth]);
+    let endpoint = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        let listener = TcpListener::bind(addr).unwrap();
+        let _ = listener.accept();
+    });
+    let connect = || TcpStream::connect(addr).map_err(|e| e.to_string());
+
+    let mut attempts = 0;
+    let result = retry_with_backoff(1, Duration::from_millis(10), || {
+        attempts += 1;
+        connect()
+    });
+    assert!(result.is_err());
+    assert_eq!(attempts, 2);
+
+    let mut attempts = 0;
+    let result = retry_with_backoff(8, Duration::from_millis(10), || {
+        attempts += 1;
+        connect()
+    });
+    assert!(result.is_ok());
+    assert!(attempts > 2);
+    endpoint.join().unwrap();
+}
+
+#[test]
+#[ignore]
+fn test_pool_state() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(2));
+    assert!(pg.pool_state().is_none());
+
+    let mut pg = match env::var("DBCONN") {
+        Result::Ok(val) => PostgisDatasource::new(&val, Some(2)),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    };
+    pg.pool_stats_interval = Some(1);
+    let pg = pg.connected();
+    let conn = pg.conn().unwrap();
+    let state = pg.pool_state().unwrap();
+    assert_eq!(state.connections, 2);
+    assert_eq!(state.idle_connections, 1);
+    drop(conn);
+    assert_eq!(pg.pool_state().unwrap().idle_connections, 2);
+    assert_eq!(pg.pool_states().len(), 1);
+}
+
+#[test]
+fn test_feature_query() {
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("points");
+    // Geometry expressions without removing empty geometries
+    layer.skip_empty = false;
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+
+    // reprojection
+    layer.srid = Some(2056);
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),2056)");
+    layer.no_transform = true;
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,2056)");
+    layer.no_transform = false;
+    layer.srid = Some(4326);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326)"
+    );
+    layer.shift_longitude = true;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Shift_Longitude(ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326))"
+    );
+    pg.postgis_version = Some((3, 1));
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_ShiftLongitude(ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326))"
+    );
+    pg.postgis_version = None;
+    layer.shift_longitude = false;
+    // Output in WGS84, spatial filter in grid SRID
+    layer.srid = Some(3857);
+    layer.output_srid = Some(4326);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(geometry,4326) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.srid = Some(4326);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326)"
+    );
+    layer.output_srid = None;
+    layer.srid = Some(-1);
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,-1)");
+    layer.srid = Some(3857);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+
+    // clipping
+    layer.buffer_size = Some(10.0);
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Intersection(geometry,ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
+    layer.make_valid = true;
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Intersection(ST_MakeValid(geometry),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
+    layer.geometry_type = Some("POLYGON".to_string());
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Multi(ST_Buffer(ST_Intersection(ST_MakeValid(geometry),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)), 0.0)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
+    layer.make_valid_params = Some("method=structure".to_string());
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Multi(ST_Buffer(ST_Intersection(ST_MakeValid(geometry,'method=structure'),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)), 0.0)) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
+    // make_valid without clipping
+    layer.buffer_size = None;
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Multi(ST_MakeValid(geometry,'method=structure')) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    layer.buffer_size = Some(10.0);
+    layer.make_valid_params = None;
+    // without zero-buffer
+    layer.clip_buffer_zero = false;
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Multi(ST_Intersection(ST_MakeValid(geometry),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8))) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
+    layer.clip_buffer_zero = true;
+    layer.geometry_type = Some("POINT".to_string());
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)");
+    layer.buffer_size = Some(0.0);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.buffer_size = Some(0.5);
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),0.5*$5::FLOAT8)");
+    // buffer in grid units
+    layer.buffer_size = Some(250.0);
+    layer.buffer_units = BufferUnits::Ground;
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),250)");
+    layer.buffer_units = BufferUnits::Pixels;
+
+    layer.buffer_size = None;
+    layer.make_valid = false;
+    layer.geometry_type = Some("POLYGON".to_string());
+
+    // simplification
+    layer.simplify = true;
+    layer.tolerance = "!pixel_width!/2".to_string();
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT COALESCE(ST_MakeValid(ST_SnapToGrid(ST_Multi(geometry), $5::FLOAT8/2)),ST_GeomFromText(\'MULTIPOLYGON EMPTY\',3857))::geometry(MULTIPOLYGON,3857) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    layer.geometry_type = Some("LINESTRING".to_string());
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Multi(ST_SimplifyPreserveTopology(ST_Multi(geometry),$5::FLOAT8/2)) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    layer.tolerance = "0.5".to_string();
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Multi(ST_SimplifyPreserveTopology(ST_Multi(geometry),0.5)) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    // tolerance per zoom level
+    layer.zoom_tolerance.insert(12, "0.1".to_string());
+    assert_eq!(pg.build_query(&layer, 3857, 12, None).unwrap().sql,
+               "SELECT ST_Multi(ST_SimplifyPreserveTopology(ST_Multi(geometry),0.1)) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    assert!(pg
+        .build_query(&layer, 3857, 10, None)
+        .unwrap()
+        .sql
+        .contains("ST_SimplifyPreserveTopology(ST_Multi(geometry),0.5)"));
+    layer.zoom_tolerance.clear();
+    layer.geometry_type = Some("POINT".to_string());
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+
+    layer.simplify = false;
+    layer.query_limit = Some(1);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        // No LIMIT clause added - limited when retrieving records
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+
+    // user queries
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from("SELECT geometry AS geom FROM osm_place_point")),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    layer.query_limit = None;
+    assert_eq!(pg.build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+                   .unwrap()
+                   .sql,
+               "SELECT * FROM (SELECT geometry AS geom FROM osm_place_point) AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from(
+            "SELECT * FROM osm_place_point WHERE name='Bern'",
+        )),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    assert_eq!(pg.build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+                   .unwrap()
+                   .sql,
+               "SELECT * FROM (SELECT * FROM osm_place_point WHERE name='Bern') AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+
+    // out of maxzoom
+    //assert_eq!(pg.query(&layer, 23).unwrap().sql,
+    //    "SELECT * FROM (SELECT geometry FROM osm_place_point) AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    //layer.table_name = None;
+    //assert!(pg.query(&layer, 23).is_none());
+}
+
+#[test]
+fn test_query_params() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("buildings");
+    layer.geometry_field = Some(String::from("way"));
+
+    layer.query = vec![LayerQuery {
+                           minzoom: 0,
+                           maxzoom: Some(22),
+                           simplify: None,
+                           tolerance: None,
+                           sql: Some(String::from("SELECT name, type, 0 as osm_id, ST_Union(geometry) AS way FROM osm_buildings_gen0 WHERE geometry && !bbox!")),
+                           filter: None,
+                           group_by: None,
+                           aggregate: None,
+                       }];
+    let query = pg
+        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+        .unwrap();
+    assert_eq!(query.sql,
+               "SELECT * FROM (SELECT name, type, 0 as osm_id, ST_Union(geometry) AS way FROM osm_buildings_gen0 WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _q");
+    assert_eq!(query.params, [QueryParam::Bbox]);
+
+    layer.query = vec![LayerQuery {
+                           minzoom: 0,
+                           maxzoom: Some(22),
+                           simplify: None,
+                           tolerance: None,
+                           sql: Some(String::from("SELECT osm_id, geometry, typen FROM landuse_z13toz14n WHERE !zoom! BETWEEN 13 AND 14) AS landuse_z9toz14n")),
+                           filter: None,
+                           group_by: None,
+                           aggregate: None,
+                       }];
+    let query = pg
+        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+        .unwrap();
+    assert_eq!(query.sql,
+               "SELECT * FROM (SELECT osm_id, geometry, typen FROM landuse_z13toz14n WHERE $5 BETWEEN 13 AND 14) AS landuse_z9toz14n) AS _q WHERE way && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    assert_eq!(query.params, [QueryParam::Bbox, QueryParam::Zoom]);
+
+    layer.query = vec![LayerQuery {
+                           minzoom: 0,
+                           maxzoom: Some(22),
+                           simplify: None,
+                           tolerance: None,
+                           sql: Some(String::from("SELECT name, type, 0 as osm_id, ST_SimplifyPreserveTopology(ST_Union(geometry),!pixel_width!/2) AS way FROM osm_buildings")),
+                           filter: None,
+                           group_by: None,
+                           aggregate: None,
+                       }];
+    let query = pg
+        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+        .unwrap();
+    assert_eq!(query.sql,
+               "SELECT * FROM (SELECT name, type, 0 as osm_id, ST_SimplifyPreserveTopology(ST_Union(geometry),$5::FLOAT8/2) AS way FROM osm_buildings) AS _q WHERE way && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    assert_eq!(query.params, [QueryParam::Bbox, QueryParam::PixelWidth]);
+
+    layer.query = vec![LayerQuery {
+                           minzoom: 0,
+                           maxzoom: Some(22),
+                           simplify: None,
+                           tolerance: None,
+                           sql: Some(String::from("SELECT t.name, b.way FROM buildings b JOIN tile_stats t ON t.z=!zoom! AND t.x=!tile_x! AND t.y=!tile_y! AND t.y_xyz=!tile_y_xyz!")),
+                           filter: None,
+                           group_by: None,
+                           aggregate: None,
+                       }];
+    let query = pg
+        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+        .unwrap();
+    assert_eq!(query.sql,
+               "SELECT * FROM (SELECT t.name, b.way FROM buildings b JOIN tile_stats t ON t.z=$5 AND t.x=$6 AND t.y=$7 AND t.y_xyz=$8) AS _q WHERE way && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    assert_eq!(
+        query.params,
+        [
+            QueryParam::Bbox,
+            QueryParam::Zoom,
+            QueryParam::TileX,
+            QueryParam::TileY,
+            QueryParam::TileYXyz
+        ]
+    );
+
+    // user defined parameters
+    layer
+        .params
+        .insert("tenant".to_string(), ParamValue::String("acme".to_string()));
+    layer
+        .params
+        .insert("min_height".to_string(), ParamValue::Float(3.5));
+    layer.query = vec![LayerQuery {
+                           minzoom: 0,
+                           maxzoom: Some(22),
+                           simplify: None,
+                           tolerance: None,
+                           sql: Some(String::from("SELECT name, way FROM buildings WHERE tenant=!tenant! AND height>=!min_height! AND zoom<=!zoom!")),
+                           filter: None,
+                           group_by: None,
+                           aggregate: None,
+                       }];
+    let query = pg
+        .build_query(&layer, 3857, 10, layer.query[0].sql.as_ref())
+        .unwrap();
+    assert_eq!(query.sql,
+               "SELECT * FROM (SELECT name, way FROM buildings WHERE tenant=$7::TEXT AND height>=$6::FLOAT8 AND zoom<=$5) AS _q WHERE way && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    assert_eq!(
+        query.params,
+        [
+            QueryParam::Bbox,
+            QueryParam::Zoom,
+            QueryParam::User("min_height".to_string()),
+            QueryParam::User("tenant".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_single_geometry_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("lines");
+    // Geometry expressions without removing empty geometries
+    layer.skip_empty = false;
+    layer.table_name = Some(String::from("osm_road_linestring"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some("LINESTRING".to_string());
+    layer.srid = Some(3857);
+    assert!(layer.force_multi);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(geometry) AS geometry FROM osm_road_linestring WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.force_multi = false;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_road_linestring WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.simplify = true;
+    layer.tolerance = "0.5".to_string();
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_SimplifyPreserveTopology(geometry,0.5) AS geometry FROM osm_road_linestring WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.geometry_type = Some("POLYGON".to_string());
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT COALESCE(ST_MakeValid(ST_SnapToGrid(geometry, 0.5)),ST_GeomFromText('POLYGON EMPTY',3857)) AS geometry FROM osm_road_linestring WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.geometry_type = Some("MULTIPOLYGON".to_string());
+    layer.simplify = false;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(geometry) AS geometry FROM osm_road_linestring WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+}
+
+#[test]
+fn test_curve_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("curves");
+    // Geometry expressions without removing empty geometries
+    layer.skip_empty = false;
+    layer.table_name = Some(String::from("curves"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some("CIRCULARSTRING".to_string());
+    layer.srid = Some(3857);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(ST_CurveToLine(geometry)) AS geometry FROM curves WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.curve_tolerance = Some("!pixel_width!/4".to_string());
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(ST_CurveToLine(geometry,$5::FLOAT8/4,1)) AS geometry FROM curves WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.curve_tolerance = Some("0.5".to_string());
+    layer.geometry_type = Some("CIRCULARSTRINGM".to_string());
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(ST_Force2D(ST_CurveToLine(geometry,0.5,1))) AS geometry FROM curves WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.geometry_type = Some("MULTICURVE".to_string());
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(ST_CurveToLine(geometry,0.5,1)) AS geometry FROM curves WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.geometry_type = Some("MULTISURFACE".to_string());
+    layer.buffer_size = Some(10.0);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(ST_Buffer(ST_Intersection(ST_CurveToLine(geometry,0.5,1),ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)), 0.0)) AS geometry FROM curves WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8)"
+    );
+}
+
+#[test]
+fn test_z_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("points");
+    // Geometry expressions without removing empty geometries
+    layer.skip_empty = false;
+    layer.table_name = Some(String::from("pointcloud"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some("POINTZ".to_string());
+    layer.srid = Some(3857);
+    assert_eq!(layer.base_geometry_type(), Some("POINT"));
+    assert_eq!(layer.dimensions(), 3);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Force3DZ(geometry) AS geometry FROM pointcloud WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.dimensions = Some(2);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Force2D(geometry) AS geometry FROM pointcloud WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.geometry_type = Some("POLYGON".to_string());
+    layer.dimensions = Some(3);
+    layer.simplify = true;
+    layer.tolerance = "!pixel_width!/2".to_string();
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT COALESCE(ST_MakeValid(ST_SnapToGrid(ST_Multi(ST_Force2D(geometry)), $5::FLOAT8/2)),ST_GeomFromText('MULTIPOLYGON EMPTY',3857))::geometry(MULTIPOLYGON,3857) AS geometry FROM pointcloud WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+}
+
+#[test]
+fn test_additional_geometry_query() {
+    use crate::core::layer::LayerGeometry;
+
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("buildings");
+    layer.table_name = Some(String::from("buildings"));
+    layer.geometry_field = Some(String::from("geom_area"));
+    layer.geometry_type = Some("POLYGON".to_string());
+    layer.srid = Some(3857);
+    layer.additional_geometry.push(LayerGeometry {
+        geometry_field: "geom_point".to_string(),
+        geometry_type: Some("POINT".to_string()),
+    });
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Multi(geom_area) AS geom_area,geom_point FROM buildings WHERE geom_area && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.srid = Some(2056);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(ST_Multi(geom_area),3857) AS geom_area,ST_Transform(geom_point,3857) AS geom_point FROM buildings WHERE geom_area && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),2056)"
+    );
+}
+
+#[test]
+fn test_point_snap_query() {
+    use crate::core::layer::PointSnap;
+
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("gps");
+    layer.table_name = Some(String::from("gps_points"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.srid = Some(3857);
+    layer.tolerance = "!pixel_width!/2".to_string();
+    layer.point_snap = Some(PointSnap::Snap);
+    // No snapping without simplify
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM gps_points WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+
+    layer.simplify = true;
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_SnapToGrid(geometry, $5::FLOAT8/2) AS geometry FROM gps_points WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+
+    layer.point_snap = Some(PointSnap::Distinct);
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT DISTINCT ON (geometry) * FROM (SELECT ST_SnapToGrid(geometry, $5::FLOAT8/2) AS geometry FROM gps_points WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _d");
+
+    layer.query_limit = Some(100);
+    layer.on_limit = LimitMode::Sample;
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query
+        .sql
+        .contains("SELECT * FROM (SELECT DISTINCT ON (geometry) * FROM (SELECT ST_AsMVTGeom("));
+    assert!(query
+        .sql
+        .ends_with(") AS _d) AS _s ORDER BY random() LIMIT 100) AS _t"));
+}
+
+#[test]
+fn test_order_by_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("places");
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.srid = Some(3857);
+    layer.order_by = Some(String::from("population DESC"));
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) ORDER BY population DESC"
+    );
+    // Not part of generated query templates
+    assert_eq!(
+        pg.build_query_sql(&layer, 3857, 10, None, true).unwrap(),
+        "SELECT geometry FROM osm_place_point WHERE geometry && !bbox!"
+    );
+
+    layer.query_limit = Some(100);
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query
+        .sql
+        .ends_with(" ORDER BY population DESC LIMIT 100) AS _t"));
+
+    layer.simplify = true;
+    layer.point_snap = Some(crate::core::layer::PointSnap::Distinct);
+    assert!(pg
+        .build_query(&layer, 3857, 10, None)
+        .unwrap()
+        .sql
+        .ends_with(") AS _d) AS _o ORDER BY population DESC"));
+
+    layer.on_limit = LimitMode::Sample;
+    assert!(!pg
+        .build_query(&layer, 3857, 10, None)
+        .unwrap()
+        .sql
+        .contains("population"));
+}
+
+#[test]
+fn test_assume_srid_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("parcels");
+    layer.table_name = Some(String::from("parcels"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.srid = Some(0);
+    layer.assume_srid = Some(2056);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(ST_SetSRID(geometry,2056),3857) AS geometry FROM parcels WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),2056)"
+    );
+    layer.srid = None;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(ST_SetSRID(geometry,2056),3857) AS geometry FROM parcels WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),2056)"
+    );
+
+    // Known SRID takes precedence
+    layer.srid = Some(3857);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM parcels WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+}
+
+#[test]
+fn test_hard_limit_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("places");
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.srid = Some(3857);
+    layer.hard_limit = Some(500);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) LIMIT 500"
+    );
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query.sql.ends_with(" LIMIT 500) AS _t"));
+
+    // Limit applied to ordered features
+    layer.order_by = Some(String::from("population DESC"));
+    assert!(pg
+        .build_query(&layer, 3857, 10, None)
+        .unwrap()
+        .sql
+        .ends_with(" ORDER BY population DESC LIMIT 500"));
+
+    // Combined with query_limit
+    layer.query_limit = Some(100);
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query
+        .sql
+        .ends_with(" ORDER BY population DESC LIMIT 500) AS _l LIMIT 100) AS _t"));
+}
+
+#[test]
+fn test_distinct_on_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("roads");
+    layer.table_name = Some(String::from("road_tags"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("LINESTRING"));
+    layer.srid = Some(3857);
+    layer.fid_field = Some(String::from("osm_id"));
+    layer.distinct_on = true;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT DISTINCT ON (osm_id) * FROM (SELECT ST_Multi(geometry) AS geometry FROM road_tags WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _f ORDER BY osm_id"
+    );
+
+    layer.order_by = Some(String::from("osm_id DESC"));
+    layer.query_limit = Some(100);
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query
+        .sql
+        .ends_with(") AS _f ORDER BY osm_id) AS _o ORDER BY osm_id DESC LIMIT 100) AS _t"));
+
+    layer.order_by = None;
+    layer.on_limit = LimitMode::Sample;
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query
+        .sql
+        .ends_with(") AS _f ORDER BY osm_id) AS _s ORDER BY random() LIMIT 100) AS _t"));
+}
+
+#[test]
+fn test_fid_expr_query() {
+    use crate::datasource::postgis_fields::is_attribute_column;
+
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("roads");
+    layer.table_name = Some(String::from("road_tags"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("LINESTRING"));
+    layer.srid = Some(3857);
+    layer.fid_field = Some(String::from("hashtext(osm_id||'-'||tag)"));
+    layer.distinct_on = true;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT DISTINCT ON (_fid) * FROM (SELECT ST_Multi(geometry) AS geometry,hashtext(osm_id||'-'||tag) AS _fid FROM road_tags WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _f ORDER BY _fid"
+    );
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query
+        .sql
+        .starts_with("SELECT ST_AsMVT(_t,'roads',4096,'geometry','_fid')"));
+    assert!(!is_attribute_column(&layer, "_fid"));
+    assert!(is_attribute_column(&layer, "osm_id"));
+}
+
+#[test]
+fn test_group_by_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("places");
+    layer.table_name = Some(String::from("ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.srid = Some(3857);
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(8),
+        simplify: None,
+        tolerance: None,
+        sql: None,
+        filter: None,
+        group_by: Some(String::from(
+            "ST_SnapToGrid(wkb_geometry, !pixel_width!*32)",
+        )),
+        aggregate: Some(String::from(
+            "count(*) AS count,max(scalerank) AS scalerank",
+        )),
+    }];
+    assert_eq!(
+        pg.build_query(&layer, 3857, 5, None).unwrap().sql,
+        "SELECT ST_Centroid(ST_Collect(wkb_geometry)) AS wkb_geometry,count(*) AS count,max(scalerank) AS scalerank FROM (SELECT wkb_geometry FROM ne_10m_populated_places WHERE wkb_geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _g GROUP BY ST_SnapToGrid(wkb_geometry, $5::FLOAT8*32)"
+    );
+    assert_eq!(
+        pg.build_query(&layer, 3857, 9, None).unwrap().sql,
+        "SELECT wkb_geometry FROM ne_10m_populated_places WHERE wkb_geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+
+    // Dissolve adjacent polygons
+    layer.table_name = Some(String::from("admin"));
+    layer.geometry_type = Some(String::from("POLYGON"));
+    layer.group_merge = GroupMerge::Union;
+    layer.query[0].aggregate = None;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 5, None).unwrap().sql,
+        "SELECT ST_Multi(ST_Union(wkb_geometry)) AS wkb_geometry,count(*) AS count FROM (SELECT ST_Multi(wkb_geometry) AS wkb_geometry FROM admin WHERE wkb_geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _g GROUP BY ST_SnapToGrid(wkb_geometry, $5::FLOAT8*32)"
+    );
+}
+
+#[test]
+fn test_filter_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("rivers");
+    layer.table_name = Some(String::from("rivers"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("LINESTRING"));
+    layer.srid = Some(3857);
+    layer.force_multi = false;
+    layer.query = vec![
+        LayerQuery {
+            minzoom: 0,
+            maxzoom: Some(7),
+            simplify: None,
+            tolerance: None,
+            sql: None,
+            filter: Some(String::from("scalerank <= 4")),
+            group_by: None,
+            aggregate: None,
+        },
+        LayerQuery {
+            minzoom: 8,
+            maxzoom: None,
+            simplify: None,
+            tolerance: None,
+            sql: None,
+            filter: None,
+            group_by: None,
+            aggregate: None,
+        },
+    ];
+    assert_eq!(
+        pg.build_query(&layer, 3857, 5, None).unwrap().sql,
+        "SELECT geometry FROM rivers WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) AND (scalerank <= 4)"
+    );
+    assert_eq!(
+        pg.build_query(&layer, 3857, 8, None).unwrap().sql,
+        "SELECT geometry FROM rivers WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    // Query template for generated config without zoom level dependent filters
+    assert_eq!(
+        pg.build_query_sql(&layer, 3857, 5, None, true).unwrap(),
+        "SELECT geometry FROM rivers WHERE geometry && !bbox!"
+    );
+
+    // User query with !bbox!
+    let sql = String::from("SELECT * FROM rivers WHERE geometry && !bbox!");
+    assert_eq!(
+        pg.build_query(&layer, 3857, 5, Some(&sql)).unwrap().sql,
+        "SELECT * FROM (SELECT * FROM rivers WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _q WHERE (scalerank <= 4)"
+    );
+}
+
+#[test]
+fn test_min_size_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("rivers");
+    layer.table_name = Some(String::from("rivers"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("LINESTRING"));
+    layer.srid = Some(3857);
+    layer.force_multi = false;
+    layer.min_area = Some(String::from("!pixel_width!*!pixel_width!"));
+    // min_area applies to polygons only
+    assert_eq!(
+        pg.build_query(&layer, 3857, 5, None).unwrap().sql,
+        "SELECT geometry FROM rivers WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+
+    layer.min_length = Some(String::from("!pixel_width!"));
+    let query = pg.build_query(&layer, 3857, 5, None).unwrap();
+    assert_eq!(
+        query.sql,
+        "SELECT geometry FROM rivers WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) AND (ST_Length(geometry) > $5::FLOAT8)"
+    );
+    assert_eq!(query.params, [QueryParam::Bbox, QueryParam::PixelWidth]);
+
+    layer.geometry_type = Some(String::from("MULTIPOLYGON"));
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: None,
+        simplify: None,
+        tolerance: None,
+        sql: None,
+        filter: Some(String::from("scalerank <= 4")),
+        group_by: None,
+        aggregate: None,
+    }];
+    assert_eq!(
+        pg.build_query(&layer, 3857, 5, None).unwrap().sql,
+        "SELECT ST_Multi(geometry) AS geometry FROM rivers WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) AND ((scalerank <= 4) AND ST_Area(geometry) > $5::FLOAT8*$5::FLOAT8)"
+    );
+}
+
+#[test]
+fn test_mixed_geometry_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("mixed");
+    layer.table_name = Some(String::from("mixed_geometries"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("GEOMETRY"));
+    layer.srid = Some(3857);
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT geometry,ST_GeometryType(geometry) AS _geometry_type FROM mixed_geometries WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+
+    // Raw geometry for generated config
+    assert_eq!(
+        pg.build_query_sql(&layer, 3857, 10, None, true).unwrap(),
+        "SELECT geometry FROM mixed_geometries WHERE geometry && !bbox!"
+    );
+}
+
+#[test]
+fn test_offline_query() {
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    assert!(pg.is_offline());
+    let mut layer = Layer::new("buildings");
+    // Geometry expressions without removing empty geometries
+    layer.skip_empty = false;
+    layer.table_name = Some(String::from("osm_buildings"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POLYGON"));
+    layer.srid = Some(4326);
+    layer.buffer_size = Some(0.0);
+    assert_eq!(
+        pg.detect_geometry_types(&layer),
+        Err("Layer 'buildings': geometry type detection requires a DB connection".to_string())
+    );
+    assert_eq!(pg.detect_layers(true).len(), 0);
+
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    assert_eq!(pg.validate_queries("ts", &layer), Vec::<String>::new());
+    assert_eq!(pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+               "SELECT ST_Transform(ST_Multi(ST_Buffer(ST_Intersection(geometry,ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326)), 0.0)),3857) AS geometry FROM osm_buildings WHERE geometry && ST_Transform(ST_MakeEnvelope($1,$2,$3,$4,3857),4326)");
+}
+
+#[test]
+fn test_active_layers() {
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    pg.active_layers = vec!["buildings".to_string()];
+    let mut buildings = Layer::new("buildings");
+    buildings.table_name = Some(String::from("osm_buildings"));
+    buildings.geometry_field = Some(String::from("geometry"));
+    buildings.geometry_type = Some(String::from("POLYGON"));
+    let mut roads = buildings.clone();
+    roads.name = String::from("roads");
+    roads.table_name = Some(String::from("osm_roads"));
+    let grid = Grid::web_mercator();
+    pg.prepare_queries("ts", &buildings, &grid);
+    pg.prepare_queries("ts", &roads, &grid);
+    let ts = "ts".to_string();
+    assert!(pg.query(&ts, &buildings.name, 10).is_some());
+    assert!(pg.query(&ts, &roads.name, 10).is_none());
+    assert!(pg.is_active_layer("buildings"));
+    assert!(!pg.is_active_layer("roads"));
+
+    pg.active_layers = Vec::new();
+    assert!(pg.is_active_layer("roads"));
+}
+
+#[test]
+fn test_tile_sql() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("buildings");
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POLYGON"));
+    layer.srid = Some(3857);
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from(
+            "SELECT * FROM osm_buildings WHERE geometry && !bbox! AND !zoom! > 1 AND type = !kind!",
+        )),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    layer.params.insert(
+        "kind".to_string(),
+        ParamValue::String("o'clock".to_string()),
+    );
+    let grid = Grid::web_mercator();
+    let (sql, values) = pg.tile_sql(&layer, &grid, 1, 0, 0).unwrap();
+    assert_eq!(sql, "SELECT * FROM (SELECT * FROM osm_buildings WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) AND $5 > 1 AND type = $6::TEXT) AS _q");
+    assert_eq!(
+        values,
+        vec![
+            "-20037508.342789248",
+            "0",
+            "0",
+            "20037508.342789248",
+            "1",
+            "'o''clock'"
+        ]
+    );
+
+    layer.minzoom = Some(5);
+    assert!(pg.tile_sql(&layer, &grid, 1, 0, 0).is_none());
+}
+
+#[test]
+fn test_remove_repeated_points_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("rivers");
+    // Geometry expressions without removing empty geometries
+    layer.skip_empty = false;
+    layer.table_name = Some(String::from("rivers"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("LINESTRING"));
+    layer.srid = Some(3857);
+    layer.simplify = true;
+    layer.tolerance = String::from("!pixel_width!/2");
+    layer.remove_repeated_points = true;
+    assert_eq!(
+        pg.build_query_sql(&layer, 3857, 10, None, false).unwrap(),
+        "SELECT ST_Multi(ST_RemoveRepeatedPoints(ST_SimplifyPreserveTopology(ST_Multi(geometry),!pixel_width!/2),!pixel_width!/2)) AS geometry FROM rivers WHERE geometry && !bbox!"
+    );
+
+    layer.force_multi = false;
+    layer.repeated_points_tolerance = Some(String::from("!pixel_width!"));
+    assert_eq!(
+        pg.build_query_sql(&layer, 3857, 10, None, false).unwrap(),
+        "SELECT ST_RemoveRepeatedPoints(ST_SimplifyPreserveTopology(geometry,!pixel_width!/2),!pixel_width!) AS geometry FROM rivers WHERE geometry && !bbox!"
+    );
+
+    // Polygons are snapped to the tolerance grid
+    layer.geometry_type = Some(String::from("POLYGON"));
+    assert!(!pg
+        .build_query_sql(&layer, 3857, 10, None, false)
+        .unwrap()
+        .contains("ST_RemoveRepeatedPoints"));
+}
+
+#[test]
+fn test_capabilities() {
+    use crate::datasource::postgis_ds::server_version_str;
+
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let caps = pg.capabilities();
+    assert_eq!(caps.postgis_version, None);
+    assert!(!caps.st_asmvt());
+    assert!(!caps.st_tileenvelope());
+
+    pg.postgis_version = Some((3, 1));
+    pg.server_version_num = Some(120004);
+    let caps = pg.capabilities();
+    assert!(caps.st_asmvt());
+    assert!(caps.st_tileenvelope());
+    assert!(caps.st_shiftlongitude());
+    assert!(!caps.st_makevalid_params());
+    assert!(caps.st_removerepeatedpoints());
+
+    assert_eq!(server_version_str(120004), "12.4");
+    assert_eq!(server_version_str(90619), "9.6.19");
+}
+
+#[test]
+fn test_tile_envelope_query() {
+    use crate::datasource::postgis_ds::tile_index;
+
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    pg.tile_envelope = true;
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.srid = Some(3857);
+    let query = pg.build_query(&layer, 3857, 10, None).unwrap();
+    assert_eq!(
+        query.sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_TileEnvelope($1,$2,$3)"
+    );
+    assert_eq!(
+        query.params,
+        [QueryParam::Zoom, QueryParam::TileX, QueryParam::TileYXyz]
+    );
+
+    layer.buffer_size = Some(10.0);
+    layer.geometry_type = Some("POINT".to_string());
+    let query = pg.build_query(&layer, 3857, 10, None).unwrap();
+    assert_eq!(
+        query.sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_TileEnvelope($1,$3,$4),10*$2::FLOAT8)"
+    );
+
+    // Other SRID
+    layer.srid = Some(2056);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Transform(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_Buffer(ST_TileEnvelope($1,$3,$4),10*$2::FLOAT8),2056)"
+    );
+    layer.no_transform = true;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_SetSRID(geometry,3857) AS geometry FROM osm_place_point WHERE geometry && ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,2056),10*$5::FLOAT8)"
+    );
+
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(33, 41, 6);
+    assert_eq!(tile_index(&grid, &extent, 6), (33, 22));
+    let extent = grid.tile_extent_xyz(486, 332, 10);
+    assert_eq!(tile_index(&grid, &extent, 10), (486, 332));
+}
+
+#[test]
+fn test_tile_envelope_grid() {
+    use crate::datasource::postgis_ds::{is_tile_envelope_grid, is_tile_extent};
+
+    let mut grid = Grid::web_mercator();
+    assert!(is_tile_envelope_grid(&grid));
+    grid.pixel_size = 0.00028 / 2.0;
+    grid.set_zoom_range(2, 14).unwrap();
+    assert!(is_tile_envelope_grid(&grid));
+    assert!(!is_tile_envelope_grid(&Grid::web_mercator_512()));
+    assert!(!is_tile_envelope_grid(&Grid::wgs84()));
+
+    let grid = Grid::web_mercator();
+    assert!(is_tile_extent(&grid, &grid.tile_extent(33, 41, 6), 6));
+    assert!(!is_tile_extent(&grid, &grid.tile_extent(33, 41, 6), 7));
+    let mut extent = grid.tile_extent(33, 41, 6);
+    extent.maxx += 1.0;
+    assert!(!is_tile_extent(&grid, &extent, 6));
+}
+
+#[test]
+fn test_tile_envelope_prepared_query() {
+    let mut pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    pg.postgis_version = Some((3, 1));
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.srid = Some(3857);
+    let grid = Grid::web_mercator();
+    pg.prepare_queries("ts", &layer, &grid);
+    let query = pg.query(&"ts".to_string(), &layer.name, 10).unwrap();
+    let tile = grid.tile_extent(486, 691, 10);
+    assert_eq!(
+        query.for_extent(&grid, &tile, 10).sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_TileEnvelope($1,$2,$3)"
+    );
+    // Extents other than a single tile are bound explicitly
+    let extent = Extent {
+        minx: tile.minx,
+        miny: tile.miny,
+        maxx: tile.maxx + 1.0,
+        maxy: tile.maxy,
+    };
+    let envelope_query = query.for_extent(&grid, &extent, 10);
+    assert_eq!(
+        envelope_query.sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    assert_eq!(envelope_query.params, [QueryParam::Bbox]);
+
+    // 2x2 metatile
+    let metatile = grid.metatile_extent(486, 690, 10, 2);
+    let envelope_query = query.for_extent(&grid, &metatile, 10);
+    assert_eq!(envelope_query.params, [QueryParam::Bbox]);
+
+    // Other grids
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator_512());
+    let query = pg.query(&"ts".to_string(), &layer.name, 10).unwrap();
+    assert_eq!(
+        query.sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    assert!(query.envelope_query.is_none());
+}
+
+#[test]
+fn test_mvt_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.srid = Some(3857);
+    assert_eq!(
+        pg.build_mvt_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_AsMVT(_t,'points',4096,'geometry') FROM (SELECT ST_AsMVTGeom(geometry,ST_MakeEnvelope($1,$2,$3,$4,3857),4096,0,false) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _t"
+    );
+
+    layer.srid = Some(2056);
+    layer.buffer_size = Some(10.0);
+    layer.geometry_type = Some("POINT".to_string());
+    layer.fid_field = Some(String::from("osm_id"));
+    layer.query_limit = Some(100);
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert_eq!(
+        query.sql,
+        "SELECT ST_AsMVT(_t,'points',4096,'geometry','osm_id') FROM (SELECT ST_AsMVTGeom(ST_Transform(geometry,3857),ST_MakeEnvelope($1,$2,$3,$4,3857),4096,160,true) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),10*$5::FLOAT8),2056) LIMIT 100) AS _t"
+    );
+    assert_eq!(query.params, [QueryParam::Bbox, QueryParam::PixelWidth]);
+
+    layer.on_limit = LimitMode::Sample;
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query.sql.ends_with(" ORDER BY random() LIMIT 100) AS _t"));
+    layer.on_limit = LimitMode::Error;
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query.sql.ends_with(" LIMIT 101) AS _t"));
+
+    layer.buffer_size = Some(250.0);
+    layer.buffer_units = BufferUnits::Ground;
+    let query = pg.build_mvt_query(&layer, 3857, 10, None).unwrap();
+    assert!(query.sql.contains(
+        "4096,CEIL(250/$5::FLOAT8*4096/256)::integer,true) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_Buffer(ST_MakeEnvelope($1,$2,$3,$4,3857),250),2056)"
+    ));
+}
+
+#[test]
+fn test_sample_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", Some(1));
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("osm_place_point"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.srid = Some(3857);
+    layer.on_limit = LimitMode::Sample;
+    // Sampling requires a query_limit
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    layer.query_limit = Some(100);
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT * FROM (SELECT geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _s ORDER BY random() LIMIT 100"
+    );
+}
+
+#[test]
+#[ignore]
+fn test_retrieve_features() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    let grid = Grid::web_mercator();
+    let extent = Extent {
+        minx: 821850.9,
+        miny: 5909499.5,
+        maxx: 860986.7,
+        maxy: 5948635.3,
+    };
+
+    let mut reccnt = 0;
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    pg.retrieve_features("ts", &layer, &extent, 10, &grid, None, |feat| {
+        assert_eq!(
+            "Ok(Point(Point { x: 831219.9062494118, y: 5928485.165733484, srid: Some(3857) }))",
+            &*format!("{:?}", feat.geometry())
+        );
+        assert_eq!(4, feat.attributes().len());
+        assert_eq!(None, feat.fid());
+        reccnt += 1;
+    })
+    .unwrap();
+    assert_eq!(1, reccnt);
+
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from("SELECT * FROM ne.ne_10m_populated_places")),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    layer.fid_field = Some(String::from("fid"));
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    pg.retrieve_features("ts", &layer, &extent, 10, &grid, None, |feat| {
+        assert_eq!(
+            "Ok(Point(Point { x: 831219.9062494118, y: 5928485.165733484, srid: Some(3857) }))",
+            &*format!("{:?}", feat.geometry())
+        );
+        assert_eq!(feat.attributes()[0].key, "scalerank"); //Numeric
+        assert_eq!(feat.attributes()[1].key, "name");
+        assert_eq!(feat.attributes()[2].key, "pop_max"); //Numeric
+        assert_eq!(feat.attributes()[0].value, FeatureAttrValType::Int(4));
+        assert_eq!(
+            feat.attributes()[1].value,
+            FeatureAttrValType::String("Bern".to_string())
+        );
+        assert_eq!(feat.fid(), Some(6478));
+    })
+    .unwrap();
+}
+
+#[test]
+#[ignore]
+fn test_spatial_index() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    assert_eq!(pg.has_spatial_index(&layer), Some(true));
+
+    // Created with CREATE TABLE AS
+    layer.table_name = Some(String::from("ne.rivers_lake_centerlines"));
+    assert_eq!(pg.has_spatial_index(&layer), Some(false));
+
+    layer.table_name = None;
+    assert_eq!(pg.has_spatial_index(&layer), None);
+}
+
+#[test]
+#[ignore]
+fn test_detect_versions() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let caps = pg.capabilities();
+    assert!(caps.postgis_version >= Some((2, 0)));
+    assert!(caps.server_version_num >= Some(90000));
+}
+
+#[test]
+#[ignore]
+fn test_detect_srid() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    assert_eq!(pg.detect_srid(&layer), Some(3857));
+
+    layer.geometry_field = Some(String::from("fid"));
+    assert_eq!(pg.detect_srid(&layer), None);
+
+    layer.table_name = None;
+    assert_eq!(pg.detect_srid(&layer), None);
+}
+
+#[test]
+#[ignore]
+fn test_validate_queries() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    assert_eq!(pg.validate_queries("ts", &layer), Vec::<String>::new());
+
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from(
+            "SELECT wkb_geometry,dropped_column FROM ne.ne_10m_populated_places",
+        )),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    let errors = pg.validate_queries("ts", &layer);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("Layer 'points': "));
+    assert!(errors[0].contains("dropped_column"));
+
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: None,
+        filter: Some(String::from("scalerank <= 'x' AND !zoom! > 2")),
+        group_by: None,
+        aggregate: None,
+    }];
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    let errors = pg.validate_queries("ts", &layer);
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0].starts_with("Layer 'points': invalid filter 'scalerank <= 'x' AND !zoom! > 2': ")
+    );
 }
 
 #[test]
 #[ignore]
-fn test_retrieve_features() {
+fn test_retrieve_mixed_features() {
     let mut pg: PostgisDatasource = match env::var("DBCONN") {
         Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
         Result::Err(_) => panic!("DBCONN undefined"),
     }
     .unwrap();
 
-    let mut layer = Layer::new("points");
-    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    let mut layer = Layer::new("mixed");
+    layer.table_name = Some(String::from("ne.mixed_geometries"));
     layer.geometry_field = Some(String::from("wkb_geometry"));
-    layer.geometry_type = Some(String::from("POINT"));
+    layer.geometry_type = Some(String::from("GEOMETRY"));
+    layer.srid = Some(3857);
+    layer.buffer_size = Some(0.0);
     let grid = Grid::web_mercator();
     let extent = Extent {
         minx: 821850.9,
@@ -308,43 +2086,21 @@ fn test_retrieve_features() {
         maxy: 5948635.3,
     };
 
-    let mut reccnt = 0;
-    pg.prepare_queries("ts", &layer, 3857);
-    pg.retrieve_features("ts", &layer, &extent, 10, &grid, |feat| {
-        assert_eq!(
-            "Ok(Point(Point { x: 831219.9062494118, y: 5928485.165733484, srid: Some(3857) }))",
-            &*format!("{:?}", feat.geometry())
-        );
-        assert_eq!(4, feat.attributes().len());
-        assert_eq!(None, feat.fid());
-        reccnt += 1;
-    });
-    assert_eq!(1, reccnt);
-
-    layer.query = vec![LayerQuery {
-        minzoom: 0,
-        maxzoom: Some(22),
-        simplify: None,
-        tolerance: None,
-        sql: Some(String::from("SELECT * FROM ne.ne_10m_populated_places")),
-    }];
-    layer.fid_field = Some(String::from("fid"));
-    pg.prepare_queries("ts", &layer, 3857);
-    pg.retrieve_features("ts", &layer, &extent, 10, &grid, |feat| {
-        assert_eq!(
-            "Ok(Point(Point { x: 831219.9062494118, y: 5928485.165733484, srid: Some(3857) }))",
-            &*format!("{:?}", feat.geometry())
-        );
-        assert_eq!(feat.attributes()[0].key, "scalerank"); //Numeric
-        assert_eq!(feat.attributes()[1].key, "name");
-        assert_eq!(feat.attributes()[2].key, "pop_max"); //Numeric
-        assert_eq!(feat.attributes()[0].value, FeatureAttrValType::Int(4));
-        assert_eq!(
-            feat.attributes()[1].value,
-            FeatureAttrValType::String("Bern".to_string())
-        );
-        assert_eq!(feat.fid(), Some(6478));
-    });
+    let (mut points, mut polygons) = (0, 0);
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
+    pg.retrieve_features("ts", &layer, &extent, 10, &grid, None, |feat| {
+        match feat.geometry() {
+            Ok(GeometryType::Point(_)) => points += 1,
+            Ok(GeometryType::Polygon(_)) | Ok(GeometryType::MultiPolygon(_)) => polygons += 1,
+            geom => panic!("Unexpected geometry {:?}", geom),
+        }
+        // Geometry type column is not an attribute
+        assert_eq!(feat.attributes()[0].key, "name");
+        assert_eq!(1, feat.attributes().len());
+    })
+    .unwrap();
+    assert_eq!(1, points);
+    assert!(polygons > 0);
 }
 
 #[test]
@@ -361,7 +2117,7 @@ fn test_no_geom_field() {
     layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
     //layer.geometry_field = Some(String::from("wkb_geometry"));
     layer.geometry_type = Some(String::from("POINT"));
-    pg.prepare_queries("ts", &layer, 3857);
+    pg.prepare_queries("ts", &layer, &Grid::web_mercator());
 }
 
 #[test]
@@ -381,3 +2137,389 @@ fn test_tls() {
     //assert!(conn.unwrap().execute("SELECT 1::VARCHAR", &[]).is_ok());
     // Check pg_stat_ssl? https://www.postgresql.org/docs/9.6/static/monitoring-stats.html#PG-STAT-SSL-VIEW
 }
+
+#[test]
+fn test_tls_certificate_files() {
+    use crate::datasource::postgis_ds::SslMode;
+
+    let mut pg = PostgisDatasource::new("postgresql://t_rex@127.0.0.1/t_rex", Some(1));
+    assert!(pg.tls_negotiator().is_ok());
+
+    pg.sslrootcert = Some("missing/root.crt".to_string());
+    let err = pg.tls_negotiator().err().unwrap();
+    assert!(err.starts_with("Couldn't read sslrootcert 'missing/root.crt'"));
+
+    pg.sslrootcert = Some("Cargo.toml".to_string());
+    let err = pg.tls_negotiator().err().unwrap();
+    assert!(err.starts_with("Invalid sslrootcert 'Cargo.toml'"));
+
+    pg.sslrootcert = None;
+    pg.sslcert = Some("missing/client.p12".to_string());
+    let err = pg.tls_negotiator().err().unwrap();
+    assert!(err.starts_with("Couldn't read sslcert 'missing/client.p12'"));
+
+    // Missing files fail before connecting
+    pg.sslmode = SslMode::Require;
+    let err = pg.try_connected().err().unwrap();
+    assert!(err.contains("Couldn't read sslcert 'missing/client.p12'"));
+
+    pg.sslcert = None;
+    pg.sslcert_password = Some("secret".to_string());
+    assert_eq!(
+        pg.tls_negotiator().err(),
+        Some("sslcert_password requires sslcert".to_string())
+    );
+}
+
+#[test]
+fn test_ssl_runtime_config() {
+    let mut pg = PostgisDatasource::new("postgresql://t_rex@127.0.0.1/t_rex", Some(1));
+    pg.sslcert = Some(r#"C:\certs\client "t_rex".p12"#.to_string());
+    pg.sslrootcert = Some(r"C:\certs\root.crt".to_string());
+    let config = pg.gen_runtime_config().parse::<toml::Value>().unwrap();
+    let ds_cfg = &config["datasource"][0];
+    assert_eq!(ds_cfg["sslcert"].as_str(), pg.sslcert.as_deref());
+    assert_eq!(ds_cfg["sslrootcert"].as_str(), pg.sslrootcert.as_deref());
+}
+
+#[test]
+fn test_validation_query_runtime_config() {
+    let mut pg = PostgisDatasource::new("postgresql://t_rex@127.0.0.1/t_rex", Some(1));
+    pg.validation_query = Some(r#"SELECT "ok" FROM health WHERE name = 'db\1'"#.to_string());
+    let config = pg.gen_runtime_config().parse::<toml::Value>().unwrap();
+    assert_eq!(
+        config["datasource"][0]["validation_query"].as_str(),
+        pg.validation_query.as_deref()
+    );
+}
+
+#[test]
+fn test_table_filter_runtime_config() {
+    let mut pg = PostgisDatasource::new("postgresql://t_rex@127.0.0.1/t_rex", Some(1));
+    pg.table_filter = Some(r#"osm_"roads"\*"#.to_string());
+    let config = pg.gen_runtime_config().parse::<toml::Value>().unwrap();
+    assert_eq!(
+        config["datasource"][0]["table_filter"].as_str(),
+        pg.table_filter.as_deref()
+    );
+}
+
+#[test]
+#[ignore]
+fn test_reconnect_on_check_out() {
+    let dbconn = env::var("DBCONN").expect("DBCONN undefined");
+    let mut pg = PostgisDatasource::new(&dbconn, Some(1));
+    pg.validation_query = Some("SELECT 1".to_string());
+    let pg = pg.connected();
+
+    let pid: i32 = {
+        let conn = pg.conn().unwrap();
+        let rows = conn.query("SELECT pg_backend_pid()", &[]).unwrap();
+        rows.get(0).get(0)
+    };
+    // Terminate the pooled connection from outside
+    let admin = Connection::connect(dbconn.as_str(), postgres::TlsMode::None).unwrap();
+    admin
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
+        .unwrap();
+
+    let conn = pg.conn().unwrap();
+    let rows = conn.query("SELECT pg_backend_pid()", &[]).unwrap();
+    let new_pid: i32 = rows.get(0).get(0);
+    assert_ne!(pid, new_pid);
+}
+
+#[test]
+#[ignore]
+fn test_query_timeout() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from(
+            "SELECT wkb_geometry FROM ne.ne_10m_populated_places, pg_sleep(1) LIMIT 1",
+        )),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    layer.query_timeout = Some(100);
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(0, 0, 0);
+    pg.prepare_queries("ts", &layer, &grid);
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, None, |_| {});
+    match result {
+        Err(DatasourceError::Query(err)) => assert!(err.contains("statement timeout")),
+        _ => panic!("statement timeout expected"),
+    }
+
+    // The connection is usable after the timeout
+    layer.query_timeout = None;
+    pg.prepare_queries("ts", &layer, &grid);
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, None, |_| {});
+    assert_eq!(result.ok(), Some(1));
+}
+
+#[test]
+#[ignore]
+fn test_explain_tile() {
+    let pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    let grid = Grid::web_mercator();
+    let plan = pg.explain_tile(&layer, &grid, 2, 1, 1).unwrap();
+    assert!(plan.contains("ne_10m_populated_places"));
+    assert!(plan.contains("Execution Time"));
+
+    layer.minzoom = Some(5);
+    assert_eq!(
+        pg.explain_tile(&layer, &grid, 2, 1, 1),
+        Err("Layer 'points': no query for zoom level 2".to_string())
+    );
+}
+
+#[test]
+#[ignore]
+fn test_retrieve_features_budget() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(0, 0, 0);
+    pg.prepare_queries("ts", &layer, &grid);
+
+    // Budget shared by two layers of a tile
+    let budget = FeatureBudget::new(10);
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, Some(&budget), |_| {});
+    assert_eq!(result, Ok(10));
+    assert!(budget.is_exhausted());
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, Some(&budget), |_| {});
+    assert_eq!(result, Ok(0));
+}
+
+#[test]
+#[ignore]
+fn test_retrieve_features_without_cursor() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.query_limit = Some(100);
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(0, 0, 0);
+    pg.prepare_queries("ts", &layer, &grid);
+
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, None, |_| {});
+    assert_eq!(result, Ok(100));
+    layer.fetch_size = Some(0);
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, None, |_| {});
+    assert_eq!(result, Ok(100));
+    layer.query_timeout = Some(10000);
+    let result = pg.retrieve_features("ts", &layer, &extent, 0, &grid, None, |_| {});
+    assert_eq!(result, Ok(100));
+}
+
+#[test]
+fn test_skip_empty_query() {
+    let pg = PostgisDatasource::new("postgresql://pi@localhost/osm2vectortiles", None);
+    let mut layer = Layer::new("buildings");
+    layer.table_name = Some(String::from("osm_buildings"));
+    layer.geometry_field = Some(String::from("geometry"));
+    layer.geometry_type = Some("POLYGON".to_string());
+    layer.srid = Some(3857);
+    layer.force_multi = false;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_buildings WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    // Clipped geometries
+    layer.buffer_size = Some(0.0);
+    layer.clip_buffer_zero = false;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT * FROM (SELECT ST_Intersection(geometry,ST_MakeEnvelope($1,$2,$3,$4,3857)) AS geometry FROM osm_buildings WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)) AS _e WHERE NOT ST_IsEmpty(geometry)"
+    );
+    layer.skip_empty = false;
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT ST_Intersection(geometry,ST_MakeEnvelope($1,$2,$3,$4,3857)) AS geometry FROM osm_buildings WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+    // Points are not clipped
+    layer.skip_empty = true;
+    layer.geometry_type = Some("POINT".to_string());
+    assert_eq!(
+        pg.build_query(&layer, 3857, 10, None).unwrap().sql,
+        "SELECT geometry FROM osm_buildings WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+    );
+}
+
+#[test]
+#[ignore]
+fn test_retrieve_features_skip_empty() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    // L-shaped polygon, whose bounding box intersects the tile, but not its interior
+    let mut layer = Layer::new("polygons");
+    layer.geometry_field = Some(String::from("geom"));
+    layer.geometry_type = Some(String::from("POLYGON"));
+    layer.srid = Some(3857);
+    layer.buffer_size = Some(0.0);
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from(
+            "SELECT ST_SetSRID('POLYGON((0 0,1000 0,1000 10,10 10,10 1000,0 1000,0 0))'::geometry,3857) AS geom",
+        )),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    let grid = Grid::web_mercator();
+    let extent = Extent {
+        minx: 500.0,
+        miny: 500.0,
+        maxx: 900.0,
+        maxy: 900.0,
+    };
+    pg.prepare_queries("ts", &layer, &grid);
+    let result = pg.retrieve_features("ts", &layer, &extent, 16, &grid, None, |_| {});
+    assert_eq!(result, Ok(0));
+
+    layer.skip_empty = false;
+    pg.prepare_queries("ts", &layer, &grid);
+    let mut reccnt = 0;
+    let result = pg.retrieve_features("ts", &layer, &extent, 16, &grid, None, |feat| {
+        assert!(feat.geometry().unwrap().is_empty());
+        reccnt += 1;
+    });
+    assert_eq!(result, Ok(1));
+    assert_eq!(reccnt, 1);
+}
+
+#[test]
+#[ignore]
+fn test_strict_types() {
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+
+    let mut layer = Layer::new("points");
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    layer.query = vec![LayerQuery {
+        minzoom: 0,
+        maxzoom: Some(22),
+        simplify: None,
+        tolerance: None,
+        sql: Some(String::from(
+            "SELECT wkb_geometry, name, '127.0.0.1'::inet AS ip FROM ne.ne_10m_populated_places",
+        )),
+        filter: None,
+        group_by: None,
+        aggregate: None,
+    }];
+    let grid = Grid::web_mercator();
+    pg.prepare_queries("ts", &layer, &grid);
+    assert_eq!(pg.validate_queries("ts", &layer), Vec::<String>::new());
+
+    pg.strict_types = true;
+    pg.prepare_queries("ts", &layer, &grid);
+    assert_eq!(
+        pg.validate_queries("ts", &layer),
+        vec!["Layer 'points': unsupported column types: ip (inet) - exclude or cast these columns"]
+    );
+
+    layer.exclude_fields = vec![String::from("ip")];
+    pg.prepare_queries("ts", &layer, &grid);
+    assert_eq!(pg.validate_queries("ts", &layer), Vec::<String>::new());
+}
+
+#[test]
+#[ignore]
+fn test_retrieve_mvt_layer_errors() {
+    use crate::datasource::postgis_ds::MvtMode;
+
+    let mut pg: PostgisDatasource = match env::var("DBCONN") {
+        Result::Ok(val) => Some(PostgisDatasource::new(&val, Some(1)).connected()),
+        Result::Err(_) => panic!("DBCONN undefined"),
+    }
+    .unwrap();
+    pg.mvt_mode = MvtMode::Postgis;
+
+    let mut layer = Layer::new("points");
+    layer.table_name = Some(String::from("ne.ne_10m_populated_places"));
+    layer.geometry_field = Some(String::from("wkb_geometry"));
+    layer.geometry_type = Some(String::from("POINT"));
+    let grid = Grid::web_mercator();
+    let extent = Extent {
+        minx: 821850.9,
+        miny: 5909499.5,
+        maxx: 860986.7,
+        maxy: 5948635.3,
+    };
+    pg.prepare_queries("ts", &layer, &grid);
+    let mvt_layer = pg.retrieve_mvt_layer("ts", &layer, &extent, 10, &grid);
+    assert_eq!(mvt_layer.unwrap().unwrap().get_features().len(), 1);
+
+    // Several populated places in Switzerland
+    let ch_extent = Extent {
+        minx: 650000.0,
+        miny: 5750000.0,
+        maxx: 1150000.0,
+        maxy: 6080000.0,
+    };
+    layer.query_limit = Some(1);
+    layer.on_limit = LimitMode::Error;
+    pg.prepare_queries("ts", &layer, &grid);
+    match pg.retrieve_mvt_layer("ts", &layer, &ch_extent, 10, &grid) {
+        Some(Err(DatasourceError::QueryLimitExceeded)) => {}
+        _ => panic!("QueryLimitExceeded expected"),
+    }
+    layer.query_limit = None;
+
+    // Query failures are not returned as empty layers
+    layer.table_name = Some(String::from("ne.missing_table"));
+    pg.prepare_queries("ts", &layer, &grid);
+    match pg.retrieve_mvt_layer("ts", &layer, &extent, 10, &grid) {
+        Some(Err(DatasourceError::Query(err))) => assert!(err.starts_with("Layer 'points': ")),
+        _ => panic!("query error expected"),
+    }
+}
@@ -31,6 +31,7 @@ impl GeometryType {
     pub fn mvt_field_type(&self) -> vector_tile::Tile_GeomType {
         match self {
             &GeometryType::Point(_) => vector_tile::Tile_GeomType::POINT,
+            &GeometryType::PointZ(_) => vector_tile::Tile_GeomType::POINT,
             &GeometryType::LineString(_) => vector_tile::Tile_GeomType::LINESTRING,
             &GeometryType::Polygon(_) => vector_tile::Tile_GeomType::POLYGON,
             &GeometryType::MultiPoint(_) => vector_tile::Tile_GeomType::POINT,
@@ -174,6 +175,11 @@ impl<'a> Tile<'a> {
             GeometryType::Point(ref g) => {
                 screen::Point::from_geom(&self.extent, self.reverse_y, tile_size, g).encode()
             }
+            GeometryType::PointZ(ref g) => {
+                // MVT has no elevation - encode as 2D point
+                let p = geom::Point::new(g.x, g.y, g.srid);
+                screen::Point::from_geom(&self.extent, self.reverse_y, tile_size, &p).encode()
+            }
             GeometryType::MultiPoint(ref g) => {
                 screen::MultiPoint::from_geom(&self.extent, self.reverse_y, tile_size, g).encode()
             }
@@ -223,7 +229,19 @@ impl<'a> Tile<'a> {
         mvt_feature.mut_tags().push(validx as u32);
     }
 
-    pub fn add_feature(&self, mut mvt_layer: &mut vector_tile::Tile_Layer, feature: &dyn Feature) {
+    pub fn add_feature(&self, mvt_layer: &mut vector_tile::Tile_Layer, feature: &dyn Feature) {
+        if let Ok(geom) = feature.geometry() {
+            self.add_feature_geometry(mvt_layer, feature, geom);
+        }
+    }
+
+    /// Add feature with a given geometry, e.g. clipped to this tile
+    pub fn add_feature_geometry(
+        &self,
+        mut mvt_layer: &mut vector_tile::Tile_Layer,
+        feature: &dyn Feature,
+        geom: geom::GeometryType,
+    ) {
         let mut mvt_feature = vector_tile::Tile_Feature::new();
         if let Some(fid) = feature.fid() {
             mvt_feature.set_id(fid);
@@ -252,6 +270,12 @@ impl<'a> Tile<'a> {
                 FeatureAttrValType::Bool(v) => {
                     mvt_value.set_bool_value(v);
                 }
+                FeatureAttrValType::Timestamp(v) => {
+                    mvt_value.set_int_value(v);
+                }
+                FeatureAttrValType::Date(ref v) => {
+                    mvt_value.set_string_value(v.clone());
+                }
                 FeatureAttrValType::VarcharArray(v) => {
                     for array_val in v {
                         Tile::add_feature_attribute(
@@ -271,14 +295,12 @@ impl<'a> Tile<'a> {
                 mvt_value,
             );
         }
-        if let Ok(geom) = feature.geometry() {
-            let g_type = geom.mvt_field_type();
-            let enc_geom = self.encode_geom(geom, mvt_layer.get_extent()).vec();
-            if !enc_geom.is_empty() {
-                mvt_feature.set_field_type(g_type);
-                mvt_feature.set_geometry(enc_geom);
-                mvt_layer.mut_features().push(mvt_feature);
-            }
+        let g_type = geom.mvt_field_type();
+        let enc_geom = self.encode_geom(geom, mvt_layer.get_extent()).vec();
+        if !enc_geom.is_empty() {
+            mvt_feature.set_field_type(g_type);
+            mvt_feature.set_geometry(enc_geom);
+            mvt_layer.mut_features().push(mvt_feature);
         }
     }
 
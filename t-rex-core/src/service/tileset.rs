@@ -6,6 +6,7 @@
 use crate::core::config::Config;
 use crate::core::config::{TilesetCacheCfg, TilesetCfg};
 use crate::core::layer::Layer;
+use std::cmp;
 use tile_grid::Extent;
 
 #[derive(Clone, Debug)]
@@ -40,6 +41,10 @@ pub struct Tileset {
     pub start_zoom: Option<u8>,
     pub layers: Vec<Layer>,
     pub cache_limits: Option<CacheLimits>,
+    /// Render tiles in blocks of `metatile` x `metatile` tiles when seeding
+    pub metatile: Option<u8>,
+    /// Stop reading layers when the tile contains `max_features` features
+    pub max_features: Option<u64>,
 }
 
 pub static WORLD_EXTENT: Extent = Extent {
@@ -83,6 +88,9 @@ impl Tileset {
     pub fn get_start_zoom(&self) -> u8 {
         self.start_zoom.unwrap_or(2)
     }
+    pub fn metatile(&self) -> u8 {
+        cmp::max(self.metatile.unwrap_or(1), 1)
+    }
     pub fn is_cachable_at(&self, zoom: u8) -> bool {
         match self.cache_limits {
             Some(ref cl) => !cl.no_cache && cl.minzoom <= zoom && cl.maxzoom.unwrap_or(99) >= zoom,
@@ -119,6 +127,8 @@ impl<'a> Config<'a, TilesetCfg> for Tileset {
             start_zoom: tileset_cfg.start_zoom.clone(),
             layers: layers,
             cache_limits: cache_limits,
+            metatile: tileset_cfg.metatile,
+            max_features: tileset_cfg.max_features,
         })
     }
     fn gen_config() -> String {
@@ -156,6 +166,8 @@ fn test_zoom() {
         }),
         layers: vec![layer],
         cache_limits: None,
+        metatile: None,
+        max_features: None,
     };
 
     assert_eq!(tileset.minzoom(), 0);
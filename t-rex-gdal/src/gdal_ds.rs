@@ -7,19 +7,23 @@ use crate::gdal_fields::*;
 use gdal;
 use gdal::spatial_ref::{CoordTransform, SpatialRef};
 use gdal::vector::{Dataset, Geometry};
+use gdal_sys;
 use std::collections::BTreeMap;
+use std::ffi::CString;
 use std::path::Path;
 use t_rex_core::core::config::DatasourceCfg;
 use t_rex_core::core::feature::Feature;
-use t_rex_core::core::layer::Layer;
+use t_rex_core::core::layer::{Layer, LimitMode};
 use t_rex_core::core::Config;
-use t_rex_core::datasource::DatasourceType;
+use t_rex_core::datasource::{DatasourceError, DatasourceType, FeatureBudget};
 use tile_grid::Extent;
 use tile_grid::Grid;
 
 #[derive(Clone)]
 pub struct GdalDatasource {
     pub path: String,
+    /// Timeout in seconds for HTTP requests of this dataset (GDAL_HTTP_TIMEOUT)
+    pub http_timeout: Option<u32>,
     // We don't store the Dataset, because we need mut access for getting layers
     /// SpatialRef WKT for layers which need CoordTransform
     geom_transform: BTreeMap<String, String>,
@@ -29,9 +33,35 @@ impl GdalDatasource {
     pub fn new(path: &str) -> GdalDatasource {
         GdalDatasource {
             path: path.to_string(),
+            http_timeout: None,
             geom_transform: BTreeMap::new(),
         }
     }
+    /// Set GDAL_HTTP_TIMEOUT for the current thread until the returned guard is dropped
+    pub(crate) fn http_timeout_option(&self) -> Option<ThreadConfigOption> {
+        self.http_timeout
+            .map(|timeout| ThreadConfigOption::set("GDAL_HTTP_TIMEOUT", &timeout.to_string()))
+    }
+}
+
+/// Thread-local GDAL configuration option, cleared when dropped
+pub(crate) struct ThreadConfigOption {
+    key: CString,
+}
+
+impl ThreadConfigOption {
+    pub(crate) fn set(key: &str, value: &str) -> ThreadConfigOption {
+        let key = CString::new(key).unwrap();
+        let value = CString::new(value).unwrap();
+        unsafe { gdal_sys::CPLSetThreadLocalConfigOption(key.as_ptr(), value.as_ptr()) };
+        ThreadConfigOption { key }
+    }
+}
+
+impl Drop for ThreadConfigOption {
+    fn drop(&mut self) {
+        unsafe { gdal_sys::CPLSetThreadLocalConfigOption(self.key.as_ptr(), std::ptr::null()) };
+    }
 }
 
 impl DatasourceType for GdalDatasource {
@@ -39,11 +69,13 @@ impl DatasourceType for GdalDatasource {
     fn connected(&self) -> GdalDatasource {
         GdalDatasource {
             path: self.path.clone(),
+            http_timeout: self.http_timeout,
             geom_transform: BTreeMap::new(),
         }
     }
     fn detect_layers(&self, _detect_geometry_types: bool) -> Vec<Layer> {
         let mut layers: Vec<Layer> = Vec::new();
+        let _http_timeout = self.http_timeout_option();
         let mut dataset = Dataset::open(Path::new(&self.path)).unwrap();
         for idx in 0..dataset.count() {
             let gdal_layer = dataset.layer(idx).unwrap();
@@ -76,6 +108,7 @@ impl DatasourceType for GdalDatasource {
         transform_extent(extent, 4326, dest_srid).ok()
     }
     fn layer_extent(&self, layer: &Layer, grid_srid: i32) -> Option<Extent> {
+        let _http_timeout = self.http_timeout_option();
         let mut dataset = Dataset::open(Path::new(&self.path)).unwrap();
         let layer_name = layer.table_name.as_ref().unwrap();
         let ogr_layer = dataset.layer_by_name(layer_name).unwrap();
@@ -125,7 +158,8 @@ impl DatasourceType for GdalDatasource {
             None => None,
         }
     }
-    fn prepare_queries(&mut self, _tileset: &str, layer: &Layer, grid_srid: i32) {
+    fn prepare_queries(&mut self, _tileset: &str, layer: &Layer, grid: &Grid) {
+        let grid_srid = grid.srid;
         if !Path::new(&self.path).exists() {
             warn!(
                 "Layer '{}': Can't open dataset '{}'",
@@ -133,6 +167,7 @@ impl DatasourceType for GdalDatasource {
             );
             // We continue, because GDAL also supports HTTP adresses
         }
+        let _http_timeout = self.http_timeout_option();
         let mut dataset = Dataset::open(Path::new(&self.path)).unwrap();
         let layer_name = layer.table_name.as_ref().unwrap();
         let ogr_layer = dataset.layer_by_name(layer_name);
@@ -145,6 +180,71 @@ impl DatasourceType for GdalDatasource {
         }
         let ogr_layer = ogr_layer.unwrap();
 
+        if let Some(query_limit) = layer.query_limit {
+            if layer.on_limit == LimitMode::Sample {
+                warn!(
+                    "Layer '{}': on_limit mode 'sample' not supported for GDAL datasources - truncating to query_limit {}",
+                    layer.name, query_limit
+                );
+            } else {
+                info!(
+                    "Layer '{}': query_limit {} with on_limit mode '{}'",
+                    layer.name,
+                    query_limit,
+                    layer.on_limit.as_str()
+                );
+            }
+        }
+
+        if layer.output_srid.is_some() {
+            warn!(
+                "Layer '{}': output_srid not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.distinct_on {
+            warn!(
+                "Layer '{}': distinct_on not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.query.iter().any(|q| q.filter.is_some()) {
+            warn!(
+                "Layer '{}': query filters not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.fid_expr().is_some() {
+            warn!(
+                "Layer '{}': fid_field expressions not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.remove_repeated_points {
+            warn!(
+                "Layer '{}': remove_repeated_points not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.min_area.is_some() || layer.min_length.is_some() {
+            warn!(
+                "Layer '{}': min_area and min_length not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.query.iter().any(|q| q.group_by.is_some()) {
+            warn!(
+                "Layer '{}': group_by not supported for GDAL datasources",
+                layer.name
+            );
+        }
+        if layer.hard_limit.is_some() {
+            warn!(
+                "Layer '{}': hard_limit not supported for GDAL datasources - use query_limit",
+                layer.name
+            );
+        }
+
         let grid_sref = match SpatialRef::from_epsg(grid_srid as u32) {
             Err(e) => {
                 error!("Unable to get grid spatial reference: {}", e);
@@ -176,7 +276,7 @@ impl DatasourceType for GdalDatasource {
         }
 
         if layer.simplify {
-            if layer.geometry_type != Some("POINT".to_string()) {
+            if layer.geometry_type != Some("POINT".to_string()) || layer.point_snap.is_some() {
                 warn!(
                     "Layer '{}': Simplification not supported for GDAL layers",
                     layer.name
@@ -199,19 +299,24 @@ impl DatasourceType for GdalDatasource {
         extent: &Extent,
         zoom: u8,
         grid: &Grid,
+        budget: Option<&FeatureBudget>,
         mut read: F,
-    ) -> u64
+    ) -> Result<u64, DatasourceError>
     where
         F: FnMut(&dyn Feature),
     {
-        let mut dataset = Dataset::open(Path::new(&self.path)).unwrap();
+        let _http_timeout = self.http_timeout_option();
+        let mut dataset = Dataset::open(Path::new(&self.path)).map_err(|e| {
+            DatasourceError::Query(format!("Can't open dataset '{}': {}", self.path, e))
+        })?;
         let layer_name = layer.table_name.as_ref().unwrap();
         debug!("retrieve_features layer: {}", layer_name);
-        let ogr_layer = dataset.layer_by_name(layer_name).unwrap();
+        let ogr_layer = dataset
+            .layer_by_name(layer_name)
+            .map_err(|e| DatasourceError::Query(format!("Layer '{}': {}", layer.name, e)))?;
 
-        let mut bbox_extent = if let Some(pixels) = layer.buffer_size {
-            let pixel_width = grid.pixel_width(zoom);
-            let buf = f64::from(pixels) * pixel_width;
+        let mut bbox_extent = if layer.buffer_size.is_some() {
+            let buf = layer.buffer_width(grid.pixel_width(zoom));
             Extent {
                 minx: extent.minx - buf,
                 miny: extent.miny - buf,
@@ -233,7 +338,10 @@ impl DatasourceType for GdalDatasource {
                 Ok(extent) => bbox_extent = extent,
                 Err(e) => {
                     error!("Unable to transform {:?}: {}", bbox_extent, e);
-                    return 0;
+                    return Err(DatasourceError::Query(format!(
+                        "Unable to transform {:?}: {}",
+                        bbox_extent, e
+                    )));
                 }
             }
             transformation = CoordTransform::new(&layer_sref, &grid_sref).ok();
@@ -249,8 +357,31 @@ impl DatasourceType for GdalDatasource {
 
         let fields_defn = ogr_layer.defn().fields().collect::<Vec<_>>();
         let mut cnt = 0;
-        let query_limit = layer.query_limit.unwrap_or(0);
+        let query_limit = layer.query_limit.unwrap_or(0) as u64;
         for feature in ogr_layer.features() {
+            if cnt == query_limit && query_limit > 0 {
+                if layer.on_limit == LimitMode::Error {
+                    debug!(
+                        "Layer {}: tile query_limit {} exceeded at zoom level {}",
+                        layer.name, cnt, zoom
+                    );
+                    return Err(DatasourceError::QueryLimitExceeded);
+                }
+                info!(
+                    "Features of layer {} limited to {} (tile query_limit reached, zoom level {})",
+                    layer.name, cnt, zoom
+                );
+                break;
+            }
+            if let Some(budget) = budget {
+                if !budget.take() {
+                    info!(
+                        "Features of layer {} limited to {} (tile max_features reached, zoom level {})",
+                        layer.name, cnt, zoom
+                    );
+                    break;
+                }
+            }
             let feat = VectorFeature {
                 layer: layer,
                 fields_defn: &fields_defn,
@@ -260,15 +391,8 @@ impl DatasourceType for GdalDatasource {
             };
             read(&feat);
             cnt += 1;
-            if cnt == query_limit as u64 {
-                info!(
-                    "Features of layer {} limited to {} (tile query_limit reached, zoom level {})",
-                    layer.name, cnt, zoom
-                );
-                break;
-            }
         }
-        cnt
+        Ok(cnt)
     }
 }
 
@@ -311,7 +435,9 @@ fn transform_extent_tr(
 
 impl<'a> Config<'a, DatasourceCfg> for GdalDatasource {
     fn from_config(ds_cfg: &DatasourceCfg) -> Result<Self, String> {
-        Ok(GdalDatasource::new(ds_cfg.path.as_ref().unwrap()))
+        let mut ds = GdalDatasource::new(ds_cfg.path.as_ref().unwrap());
+        ds.http_timeout = ds_cfg.http_timeout;
+        Ok(ds)
     }
 
     fn gen_config() -> String {
@@ -319,17 +445,24 @@ impl<'a> Config<'a, DatasourceCfg> for GdalDatasource {
 [[datasource]]
 name = "ds"
 # Dataset specification (http://gdal.org/ogr_formats.html)
+# OGC API Features services are queried with the tile extent, e.g. "OAPIF:https://example.com/ogcapi"
 path = "<filename-or-connection-spec>"
+# Timeout in seconds for HTTP requests (GDAL_HTTP_TIMEOUT)
+#http_timeout = 30
 "#;
         toml.to_string()
     }
     fn gen_runtime_config(&self) -> String {
-        format!(
+        let mut config = format!(
             r#"
 [[datasource]]
 path = "{}"
 "#,
             self.path
-        )
+        );
+        if let Some(timeout) = self.http_timeout {
+            config.push_str(&format!("http_timeout = {}\n", timeout));
+        }
+        config
     }
 }
@@ -81,9 +81,9 @@ fn test_gdal_retrieve_points() {
     };
 
     let mut ds = GdalDatasource::new("../data/natural_earth.gpkg");
-    ds.prepare_queries("ts", &layer, grid.srid);
+    ds.prepare_queries("ts", &layer, &grid);
     let mut reccnt = 0;
-    ds.retrieve_features("ts", &layer, &extent, 10, &grid, |feat| {
+    ds.retrieve_features("ts", &layer, &extent, 10, &grid, None, |feat| {
         if reccnt == 0 {
             assert_eq!(
                 "Ok(Point(Point { x: 831219.91, y: 5928485.17, srid: Some(3857) }))",
@@ -104,7 +104,8 @@ fn test_gdal_retrieve_points() {
             assert_eq!(feat.fid(), Some(4));
         }
         reccnt += 1;
-    });
+    })
+    .unwrap();
     assert_eq!(reccnt, 1);
 }
 
@@ -116,7 +117,7 @@ fn test_coord_transformation() {
     layer.srid = Some(3857);
     let grid = Grid::wgs84();
     let mut ds = GdalDatasource::new("../data/natural_earth.gpkg");
-    ds.prepare_queries("ts", &layer, grid.srid);
+    ds.prepare_queries("ts", &layer, &grid);
 
     let extent_wgs84 = Extent {
         minx: 7.3828,
@@ -140,7 +141,7 @@ fn test_coord_transformation() {
     assert!(result.is_none());
 
     let mut reccnt = 0;
-    ds.retrieve_features("ts", &layer, &extent_wgs84, 10, &grid, |feat| {
+    ds.retrieve_features("ts", &layer, &extent_wgs84, 10, &grid, None, |feat| {
         if reccnt == 0 {
             assert_eq!("Ok(Point(Point { x: 7.466975462482421, y: 46.916682758667704, srid: Some(4326) }))",
                        &*format!("{:?}", feat.geometry()));
@@ -152,7 +153,8 @@ fn test_coord_transformation() {
             );
         }
         reccnt += 1;
-    });
+    })
+    .unwrap();
     assert_eq!(reccnt, 1);
 }
 
@@ -178,25 +180,27 @@ fn test_gdal_retrieve_multilines() {
     assert_eq!(gdal_layer.features().count(), 1404);
 
     let mut ds = GdalDatasource::new("../data/natural_earth.gpkg");
-    ds.prepare_queries("ts", &layer, grid.srid);
+    ds.prepare_queries("ts", &layer, &grid);
     let mut reccnt = 0;
 
     // without buffer
-    ds.retrieve_features("ds", &layer, &extent, 10, &grid, |_| {
+    ds.retrieve_features("ds", &layer, &extent, 10, &grid, None, |_| {
         reccnt += 1;
-    });
+    })
+    .unwrap();
     assert_eq!(reccnt, 0);
 
     // with buffer
-    layer.buffer_size = Some(600);
+    layer.buffer_size = Some(600.0);
 
-    ds.retrieve_features("ds", &layer, &extent, 22, &grid, |_| {
+    ds.retrieve_features("ds", &layer, &extent, 22, &grid, None, |_| {
         reccnt += 1;
-    });
+    })
+    .unwrap();
     assert_eq!(reccnt, 0);
 
     let mut reccnt = 0;
-    ds.retrieve_features("ds", &layer, &extent, 10, &grid, |feat| {
+    ds.retrieve_features("ds", &layer, &extent, 10, &grid, None, |feat| {
         assert_eq!(2, feat.attributes().len());
         assert_eq!(feat.attributes()[0].key, "scalerank");
         assert_eq!(feat.attributes()[1].key, "name");
@@ -216,7 +220,8 @@ fn test_gdal_retrieve_multilines() {
             }
         }
         reccnt += 1;
-    });
+    })
+    .unwrap();
     assert_eq!(reccnt, 5);
 }
 
@@ -236,9 +241,9 @@ fn test_gdal_retrieve_multipolys() {
     };
 
     let mut ds = GdalDatasource::new("../data/natural_earth.gpkg");
-    ds.prepare_queries("ds", &layer, grid.srid);
+    ds.prepare_queries("ds", &layer, &grid);
     let mut reccnt = 0;
-    ds.retrieve_features("ds", &layer, &extent, 10, &grid, |feat| {
+    ds.retrieve_features("ds", &layer, &extent, 10, &grid, None, |feat| {
         if reccnt == 0 {
             assert_eq!("Ok(MultiPolygon(MultiPolygonT { polygons: [PolygonT { rings: [LineStringT { points: [Point { x: 1068024.3649477786, y: 6028202.019",
                        &format!("{:?}", feat.geometry())[0..130]);
@@ -258,7 +263,8 @@ fn test_gdal_retrieve_multipolys() {
             assert_eq!(None, feat.fid());
         }
         reccnt += 1;
-    });
+    })
+    .unwrap();
     assert_eq!(reccnt, 1);
 }
 
@@ -285,3 +291,33 @@ fn test_no_transform() {
         "Some(Extent { minx: 22.32694, miny: 9.61387, maxx: 25.45679, maxy: 11.56232 })";
     assert_eq!(format!("{:.5?}", ext), extent_fake);
 }
+
+#[test]
+fn test_http_timeout() {
+    use t_rex_core::core::config::DatasourceCfg;
+    use t_rex_core::core::{parse_config, Config};
+
+    let toml = r#"
+        path = "../data/natural_earth.gpkg"
+        http_timeout = 5
+        "#;
+    let config: DatasourceCfg = parse_config(toml.to_string(), "").unwrap();
+    let ds = GdalDatasource::from_config(&config).unwrap();
+    assert_eq!(ds.http_timeout, Some(5));
+    assert!(ds.gen_runtime_config().contains("http_timeout = 5\n"));
+
+    let ds = ds.connected();
+    assert_eq!(ds.http_timeout, Some(5));
+    let http_timeout = || gdal::config::get_config_option("GDAL_HTTP_TIMEOUT", "-").unwrap();
+    {
+        let _http_timeout = ds.http_timeout_option();
+        assert_eq!(http_timeout(), "5");
+        // Timeout is set for the current thread only
+        let other = std::thread::spawn(http_timeout).join().unwrap();
+        assert_eq!(other, "-");
+    }
+    assert_eq!(http_timeout(), "-");
+    assert!(GdalDatasource::new("../data/natural_earth.gpkg")
+        .http_timeout_option()
+        .is_none());
+}
@@ -8,7 +8,7 @@ use gdal::spatial_ref::{CoordTransform, SpatialRef};
 use gdal::vector::{Dataset, FieldValue, Geometry};
 use gdal_sys;
 use std::path::Path;
-use t_rex_core::core::feature::{Feature, FeatureAttr, FeatureAttrValType};
+use t_rex_core::core::feature::{fid_hash, Feature, FeatureAttr, FeatureAttrValType};
 use t_rex_core::core::geom::{self, GeometryType};
 use t_rex_core::core::layer::Layer;
 
@@ -360,6 +360,7 @@ impl<'a> Feature for VectorFeature<'a> {
             let field_value = self.feature.field(&fid);
             match field_value {
                 Ok(FieldValue::IntegerValue(v)) => Some(v as u64),
+                Ok(FieldValue::StringValue(v)) => Some(fid_hash(&v)),
                 _ => None,
             }
         })
@@ -367,6 +368,9 @@ impl<'a> Feature for VectorFeature<'a> {
     fn attributes(&self) -> Vec<FeatureAttr> {
         let mut attrs = Vec::new();
         for (_i, field) in self.fields_defn.into_iter().enumerate() {
+            if !self.layer.selects_field(&field.name()) {
+                continue;
+            }
             let field_value = self.feature.field(&field.name()); //TODO: get by index
             let val = match field_value {
                 Ok(FieldValue::StringValue(v)) => Some(FeatureAttrValType::String(v)),
@@ -385,8 +389,9 @@ impl<'a> Feature for VectorFeature<'a> {
             // match field.field_type {
             //    OGRFieldType::OFTString => {
             if let Some(val) = val {
+                let name = field.name();
                 let fattr = FeatureAttr {
-                    key: field.name(),
+                    key: self.layer.field_alias(&name).cloned().unwrap_or(name),
                     value: val,
                 };
                 attrs.push(fattr);
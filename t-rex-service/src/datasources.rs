@@ -11,7 +11,8 @@ use t_rex_core::core::layer::Layer;
 use t_rex_core::core::Config;
 #[cfg(not(feature = "with-gdal"))]
 use t_rex_core::datasource::DummyDatasource as GdalDatasource;
-use t_rex_core::datasource::{DatasourceType, PostgisDatasource};
+use t_rex_core::datasource::{DatasourceError, DatasourceType, FeatureBudget, PostgisDatasource};
+use t_rex_core::mvt::vector_tile;
 #[cfg(feature = "with-gdal")]
 use t_rex_gdal::GdalDatasource;
 use tile_grid::{Extent, Grid};
@@ -22,6 +23,16 @@ pub enum Datasource {
     Gdal(GdalDatasource),
 }
 
+impl Datasource {
+    /// New instance with connected pool. Fails if the database is not reachable.
+    pub fn try_connected(&self) -> Result<Datasource, String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.try_connected().map(Datasource::Postgis),
+            &Datasource::Gdal(ref ds) => Ok(Datasource::Gdal(ds.connected())),
+        }
+    }
+}
+
 impl DatasourceType for Datasource {
     fn connected(&self) -> Datasource {
         match self {
@@ -53,10 +64,16 @@ impl DatasourceType for Datasource {
             &Datasource::Gdal(ref ds) => ds.layer_extent(layer, grid_srid),
         }
     }
-    fn prepare_queries(&mut self, tileset: &str, layer: &Layer, grid_srid: i32) {
+    fn prepare_queries(&mut self, tileset: &str, layer: &Layer, grid: &Grid) {
         match self {
-            &mut Datasource::Postgis(ref mut ds) => ds.prepare_queries(tileset, layer, grid_srid),
-            &mut Datasource::Gdal(ref mut ds) => ds.prepare_queries(tileset, layer, grid_srid),
+            &mut Datasource::Postgis(ref mut ds) => ds.prepare_queries(tileset, layer, grid),
+            &mut Datasource::Gdal(ref mut ds) => ds.prepare_queries(tileset, layer, grid),
+        }
+    }
+    fn validate_queries(&self, tileset: &str, layer: &Layer) -> Vec<String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.validate_queries(tileset, layer),
+            &Datasource::Gdal(ref ds) => ds.validate_queries(tileset, layer),
         }
     }
     fn retrieve_features<F>(
@@ -66,18 +83,34 @@ impl DatasourceType for Datasource {
         extent: &Extent,
         zoom: u8,
         grid: &Grid,
+        budget: Option<&FeatureBudget>,
         read: F,
-    ) -> u64
+    ) -> Result<u64, DatasourceError>
     where
         F: FnMut(&dyn Feature),
     {
         match self {
             &Datasource::Postgis(ref ds) => {
-                ds.retrieve_features(tileset, layer, extent, zoom, grid, read)
+                ds.retrieve_features(tileset, layer, extent, zoom, grid, budget, read)
             }
             &Datasource::Gdal(ref ds) => {
-                ds.retrieve_features(tileset, layer, extent, zoom, grid, read)
+                ds.retrieve_features(tileset, layer, extent, zoom, grid, budget, read)
+            }
+        }
+    }
+    fn retrieve_mvt_layer(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+    ) -> Option<Result<vector_tile::Tile_Layer, DatasourceError>> {
+        match self {
+            &Datasource::Postgis(ref ds) => {
+                ds.retrieve_mvt_layer(tileset, layer, extent, zoom, grid)
             }
+            &Datasource::Gdal(ref ds) => ds.retrieve_mvt_layer(tileset, layer, extent, zoom, grid),
         }
     }
 }
@@ -226,6 +259,95 @@ fn test_datasource_from_config() {
         pg.connection_url,
         "postgresql://pi@localhost/natural_earth_vectors"
     );
+    assert_eq!(pg.pool_size, None);
+    assert!(!pg.prewarm);
+    assert!(pg.test_on_check_out);
+    assert_eq!(pg.validation_query, None);
+    assert_eq!(pg.fetch_size, 50);
+    assert_eq!(pg.detect_limit, 1000);
+
+    let toml = r#"
+        #[[datasource]]
+        dbconn = "postgresql://pi@localhost/natural_earth_vectors"
+        pool = 16
+        connection_timeout = 5
+        sslmode = "require"
+        mvt_mode = "postgis"
+        prewarm = true
+        test_on_check_out = false
+        validation_query = "SELECT 1"
+        fetch_size = 500
+        detect_limit = 0
+        include_schemas = ["tiles", "osm"]
+        table_filter = "osm_*"
+        "#;
+    let ds = ds_from_config(toml).unwrap();
+    assert_eq!(
+        ds.gen_runtime_config(),
+        r#"
+[[datasource]]
+dbconn = "postgresql://pi@localhost/natural_earth_vectors"
+pool = 16
+connection_timeout = 5
+sslmode = "require"
+mvt_mode = "postgis"
+prewarm = true
+test_on_check_out = false
+validation_query = "SELECT 1"
+fetch_size = 500
+detect_limit = 0
+include_schemas = ["tiles", "osm"]
+table_filter = "osm_*"
+"#
+    );
+}
+
+#[test]
+fn test_datasource_replicas_from_config() {
+    let toml = r#"
+        #[[datasource]]
+        dbconn = ["postgresql://pi@primary/natural_earth_vectors", "postgresql://pi@replica/natural_earth_vectors"]
+        "#;
+    let ds = ds_from_config(toml).unwrap();
+    assert_eq!(
+        ds.gen_runtime_config(),
+        r#"
+[[datasource]]
+dbconn = ["postgresql://pi@primary/natural_earth_vectors", "postgresql://pi@replica/natural_earth_vectors"]
+"#
+    );
+    let pg = match ds {
+        Datasource::Postgis(pg) => pg,
+        _ => panic!(),
+    };
+    assert_eq!(
+        pg.connection_url,
+        "postgresql://pi@primary/natural_earth_vectors"
+    );
+    assert_eq!(
+        pg.replica_urls,
+        vec!["postgresql://pi@replica/natural_earth_vectors".to_string()]
+    );
+}
+
+#[test]
+fn test_datasource_url_from_env() {
+    use std::env;
+
+    env::set_var(
+        "TREX_DATASOURCE_URL_ENV_TEST",
+        "postgresql://pi@localhost/from_env",
+    );
+    let toml = r#"
+        #[[datasource]]
+        name = "env-test"
+        dbconn = "postgresql://pi@localhost/natural_earth_vectors"
+        "#;
+    let pg = match ds_from_config(toml).unwrap() {
+        Datasource::Postgis(pg) => pg,
+        _ => panic!(),
+    };
+    assert_eq!(pg.connection_url, "postgresql://pi@localhost/from_env");
 }
 
 #[test]
@@ -250,7 +372,29 @@ fn test_datasource_config_errors() {
         "#;
     assert_eq!(
         ds_from_config(toml).err(),
-        Some(" - invalid type: boolean `true`, expected a string for key `dbconn`".to_string())
+        Some(
+            " - data did not match any variant of untagged enum DbConnCfg for key `dbconn`"
+                .to_string()
+        )
+    );
+
+    let toml = r#"
+        #[[datasource]]
+        dbconn = []
+        "#;
+    assert_eq!(
+        ds_from_config(toml).err(),
+        Some("dbconn undefined".to_string())
+    );
+
+    let toml = r#"
+        #[[datasource]]
+        dbconn = "postgresql://pi@localhost/natural_earth_vectors"
+        sslmode = "allow"
+        "#;
+    assert_eq!(
+        ds_from_config(toml).err(),
+        Some("Unexpected enum value 'allow'".to_string())
     );
 }
 
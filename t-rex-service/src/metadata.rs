@@ -105,6 +105,13 @@ impl MvtService {
             .map(|layer| {
                 let meta = layer.metadata();
                 let query = layer.query(layer.maxzoom(22));
+                let buffer_size = layer.buffer_size.unwrap_or(0.0);
+                // Keep integer output for whole pixel buffers
+                let buffer_size = if buffer_size.fract() == 0.0 {
+                    json!(buffer_size as u64)
+                } else {
+                    json!(buffer_size)
+                };
                 let mut meta_json = json!({
                     "id": meta.get("id").unwrap(),
                     "name": meta.get("name").unwrap(),
@@ -113,7 +120,7 @@ impl MvtService {
                     "properties": {
                         "minzoom": cmp::max(ts.minzoom(), layer.minzoom()),
                         "maxzoom": cmp::min(ts.maxzoom(), layer.maxzoom(22)),
-                        "buffer-size": layer.buffer_size.unwrap_or(0)
+                        "buffer-size": buffer_size
                     },
                     "fields": {}
                 });
@@ -333,8 +340,8 @@ fn test_tilejson() {
     env::var("DBCONN").expect("DBCONN undefined");
     let config = read_config("src/test/example.toml").unwrap();
     let mut service = MvtService::from_config(&config).unwrap();
-    service.connect();
-    service.prepare_feature_queries();
+    service.connect().unwrap();
+    service.prepare_feature_queries().unwrap();
     let metadata = format!(
         "{:#}",
         service.get_tilejson("http://127.0.0.1", "osm").unwrap()
@@ -448,7 +455,7 @@ fn test_mbtiles_metadata() {
     env::var("DBCONN").expect("DBCONN undefined");
     let config = read_config("src/test/example.toml").unwrap();
     let mut service = MvtService::from_config(&config).unwrap();
-    service.connect();
+    service.connect().unwrap();
     let metadata = format!("{:#}", service.get_mbtiles_metadata("osm").unwrap());
     println!("{}", metadata);
     let expected = r#"{
@@ -7,18 +7,41 @@ use crate::datasources::{Datasource, Datasources};
 use pbr::ProgressBar;
 use percent_encoding::percent_decode;
 use serde_json;
-use std::cmp;
+use std::collections::HashSet;
 use std::io::{stderr, Stderr, Stdout};
 use std::time::Instant;
 use t_rex_core::cache::{Cache, Tilecache};
-use t_rex_core::core::layer::Layer;
+use t_rex_core::core::layer::{Layer, LayerQuery};
 use t_rex_core::core::stats::Statistics;
 use t_rex_core::core::{ApplicationCfg, Config};
-use t_rex_core::datasource::DatasourceType;
+use t_rex_core::datasource::{DatasourceError, DatasourceType, FeatureBudget};
 use t_rex_core::mvt::tile::Tile;
 use t_rex_core::mvt::vector_tile;
 use t_rex_core::service::tileset::{Tileset, WORLD_EXTENT};
-use tile_grid::{extent_to_merc, Extent, ExtentInt, Grid, GridIterator};
+use tile_grid::{extent_to_merc, Extent, Grid, GridIterator};
+
+/// Take the features of a layer encoded by the datasource from `budget`.
+///
+/// Features exceeding the budget are removed. Returns the remaining feature count.
+pub(crate) fn take_features(
+    mvt_layer: &mut vector_tile::Tile_Layer,
+    budget: &FeatureBudget,
+    zoom: u8,
+) -> u64 {
+    let max_features = budget.remaining() as usize;
+    if mvt_layer.get_features().len() > max_features {
+        info!(
+            "Features of layer {} limited to {} (tile max_features reached, zoom level {})",
+            mvt_layer.get_name(),
+            max_features,
+            zoom
+        );
+        mvt_layer.mut_features().truncate(max_features);
+    }
+    let num_features = mvt_layer.get_features().len() as u64;
+    budget.consume(num_features);
+    num_features
+}
 
 /// Mapbox Vector Tile Service
 #[derive(Clone)]
@@ -33,14 +56,15 @@ impl MvtService {
     /// Connect all datasources
     // Needed before calling methods on PostGIS datasources like prepare_feature_queries or get_mbtiles_metadata
     // TODO: connect automatically when needed
-    pub fn connect(&mut self) {
+    pub fn connect(&mut self) -> Result<(), String> {
         let mut datasources = Datasources::new();
         datasources.default = self.datasources.default.clone();
         for (name, ds) in &self.datasources.datasources {
-            datasources.add(&name, ds.connected());
+            datasources.add(&name, ds.try_connected()?);
         }
         datasources.setup();
         self.datasources = datasources;
+        Ok(())
     }
     pub(crate) fn ds(&self, layer: &Layer) -> Option<&Datasource> {
         self.datasources.datasource(&layer.datasource)
@@ -57,19 +81,37 @@ impl MvtService {
             None => Vec::new(),
         }
     }
+    /// Feature budget of a tile of given tileset
+    fn feature_budget(&self, tileset: &str) -> Option<FeatureBudget> {
+        self.get_tileset(tileset)
+            .and_then(|ts| ts.max_features)
+            .map(FeatureBudget::new)
+    }
     /// Prepare datasource queries. Must be called before requesting tiles.
-    pub fn prepare_feature_queries(&mut self) {
+    ///
+    /// Returns the errors of all queries failing validation by the datasource.
+    pub fn prepare_feature_queries(&mut self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
         for tileset in &self.tilesets {
             for layer in &tileset.layers {
                 let ds = self
                     .datasources
                     .datasource_mut(&layer.datasource)
                     .expect(&format!("Datasource of layer `{}` not found", layer.name));
-                ds.prepare_queries(&tileset.name, &layer, self.grid.srid);
+                ds.prepare_queries(&tileset.name, &layer, &self.grid);
+                errors.append(&mut ds.validate_queries(&tileset.name, &layer));
             }
         }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
     /// Create vector tile from input at x, y, z in TMS adressing scheme
+    ///
+    /// Layers exceeding their `query_limit` with `on_limit = "error"` are skipped.
+    /// Reading stops after `max_features` features of the tileset.
     pub fn tile(
         &self,
         tileset: &str,
@@ -77,27 +119,63 @@ impl MvtService {
         ytile: u32,
         zoom: u8,
         mut stats: Option<&mut Statistics>,
-    ) -> vector_tile::Tile {
+    ) -> Result<vector_tile::Tile, DatasourceError> {
         let extent = self.grid.tile_extent(xtile, ytile, zoom);
         debug!(
             "{}/{}/{}/{} retrieving with {:?}",
             tileset, zoom, xtile, ytile, extent
         );
         let mut tile = Tile::new(&extent, true);
+        let budget = self.feature_budget(tileset);
         for layer in self.get_tileset_layers(tileset) {
             if zoom >= layer.minzoom() && zoom <= layer.maxzoom(self.grid.maxzoom()) {
-                let mut mvt_layer = tile.new_layer(layer);
+                if budget.as_ref().map_or(false, |b| b.is_exhausted()) {
+                    debug!(
+                        "{}/{}/{}/{} layer {}: max_features reached - skipping layer",
+                        tileset, zoom, xtile, ytile, layer.name
+                    );
+                    continue;
+                }
+                let ds = self.ds(&layer).unwrap();
                 let now = Instant::now();
-                let num_features = self.ds(&layer).unwrap().retrieve_features(
-                    tileset,
-                    &layer,
-                    &extent,
-                    zoom,
-                    &self.grid,
-                    |feat| {
-                        tile.add_feature(&mut mvt_layer, feat);
-                    },
-                );
+                let (mvt_layer, num_features) =
+                    match ds.retrieve_mvt_layer(tileset, &layer, &extent, zoom, &self.grid) {
+                        Some(Ok(mut mvt_layer)) => {
+                            // Layer encoded by datasource
+                            let num_features = match budget {
+                                Some(ref budget) => take_features(&mut mvt_layer, budget, zoom),
+                                None => mvt_layer.get_features().len() as u64,
+                            };
+                            (mvt_layer, Ok(num_features))
+                        }
+                        Some(Err(err)) => (tile.new_layer(layer), Err(err)),
+                        None => {
+                            let mut mvt_layer = tile.new_layer(layer);
+                            let num_features = ds.retrieve_features(
+                                tileset,
+                                &layer,
+                                &extent,
+                                zoom,
+                                &self.grid,
+                                budget.as_ref(),
+                                |feat| {
+                                    tile.add_feature(&mut mvt_layer, feat);
+                                },
+                            );
+                            (mvt_layer, num_features)
+                        }
+                    };
+                let num_features = match num_features {
+                    Ok(num_features) => num_features,
+                    Err(DatasourceError::QueryLimitExceeded) => {
+                        warn!(
+                            "{}/{}/{}/{} layer {}: query_limit exceeded - skipping layer",
+                            tileset, zoom, xtile, ytile, layer.name
+                        );
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                };
                 let elapsed = now.elapsed();
                 if let Some(ref mut stats) = stats {
                     stats.add(
@@ -118,9 +196,168 @@ impl MvtService {
                 }
             }
         }
-        tile.mvt_tile
+        Ok(tile.mvt_tile)
+    }
+    /// Create all vector tiles of the metatile containing x, y, z in TMS adressing scheme
+    ///
+    /// Features are retrieved with one query per layer, clipped to each tile
+    /// plus its buffer and added to the tiles they intersect. Layers encoded by
+    /// the datasource are queried per tile. `max_features` applies to each tile.
+    pub fn metatile(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        meta: u8,
+        mut stats: Option<&mut Statistics>,
+    ) -> Result<Vec<(u32, u32, vector_tile::Tile)>, DatasourceError> {
+        let limits = self.grid.metatile_limits(xtile, ytile, zoom, meta);
+        let mut cells = Vec::new();
+        for y in limits.miny..limits.maxy {
+            for x in limits.minx..limits.maxx {
+                cells.push((x, y, self.grid.tile_extent(x, y, zoom)));
+            }
+        }
+        let mut tiles: Vec<(u32, u32, Tile)> = cells
+            .iter()
+            .map(|&(x, y, ref extent)| (x, y, Tile::new(extent, true)))
+            .collect();
+        let extent = self.grid.metatile_extent(xtile, ytile, zoom, meta);
+        debug!(
+            "{}/{}/{}/{} retrieving metatile with {:?}",
+            tileset, zoom, xtile, ytile, extent
+        );
+        let budgets: Vec<Option<FeatureBudget>> =
+            cells.iter().map(|_| self.feature_budget(tileset)).collect();
+        for layer in self.get_tileset_layers(tileset) {
+            if zoom < layer.minzoom() || zoom > layer.maxzoom(self.grid.maxzoom()) {
+                continue;
+            }
+            if budgets
+                .iter()
+                .all(|b| b.as_ref().map_or(false, |b| b.is_exhausted()))
+            {
+                debug!(
+                    "{}/{}/{}/{} metatile layer {}: max_features reached - skipping layer",
+                    tileset, zoom, xtile, ytile, layer.name
+                );
+                continue;
+            }
+            let ds = self.ds(&layer).unwrap();
+            let now = Instant::now();
+            let mut mvt_layers: Vec<vector_tile::Tile_Layer> = tiles
+                .iter_mut()
+                .map(|(_, _, tile)| tile.new_layer(layer))
+                .collect();
+            let mut first_mvt_layer =
+                ds.retrieve_mvt_layer(tileset, &layer, &cells[0].2, zoom, &self.grid);
+            let num_features = if first_mvt_layer.is_some() {
+                // Layers encoded by datasource
+                let mut num_features = 0;
+                for (i, &(x, y, ref tile_extent)) in cells.iter().enumerate() {
+                    let mvt_layer = match first_mvt_layer.take() {
+                        Some(mvt_layer) => mvt_layer,
+                        None => ds
+                            .retrieve_mvt_layer(tileset, &layer, tile_extent, zoom, &self.grid)
+                            .unwrap_or_else(|| Ok(vector_tile::Tile_Layer::new())),
+                    };
+                    let mut mvt_layer = match mvt_layer {
+                        Ok(mvt_layer) => mvt_layer,
+                        Err(DatasourceError::QueryLimitExceeded) => {
+                            warn!(
+                                "{}/{}/{}/{} layer {}: query_limit exceeded - skipping layer",
+                                tileset, zoom, x, y, layer.name
+                            );
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    };
+                    num_features += match budgets[i] {
+                        Some(ref budget) => take_features(&mut mvt_layer, budget, zoom),
+                        None => mvt_layer.get_features().len() as u64,
+                    };
+                    mvt_layers[i] = mvt_layer;
+                }
+                Ok(num_features)
+            } else {
+                let buffer = layer.buffer_width(self.grid.pixel_width(zoom));
+                let tile_extents: Vec<Extent> = cells
+                    .iter()
+                    .map(|&(x, y, _)| self.grid.metatile_extent_buffered(x, y, zoom, 1, buffer))
+                    .collect();
+                ds.retrieve_features(tileset, &layer, &extent, zoom, &self.grid, None, |feat| {
+                    let bbox = match feat.geometry().ok().and_then(|geom| geom.bbox()) {
+                        Some(bbox) => bbox,
+                        None => return,
+                    };
+                    for (i, (_, _, tile)) in tiles.iter().enumerate() {
+                        let ext = &tile_extents[i];
+                        let intersects = bbox.minx <= ext.maxx
+                            && bbox.maxx >= ext.minx
+                            && bbox.miny <= ext.maxy
+                            && bbox.maxy >= ext.miny;
+                        if !intersects {
+                            continue;
+                        }
+                        let geom = match feat.geometry() {
+                            // Clipped like single tiles with buffer_size
+                            Ok(geom) if layer.buffer_size.is_some() => geom.clip(ext),
+                            Ok(geom) => geom,
+                            Err(_) => continue,
+                        };
+                        if geom.is_empty() {
+                            continue;
+                        }
+                        if let Some(ref budget) = budgets[i] {
+                            if !budget.take() {
+                                continue;
+                            }
+                        }
+                        tile.add_feature_geometry(&mut mvt_layers[i], feat, geom);
+                    }
+                })
+            };
+            let num_features = match num_features {
+                Ok(num_features) => num_features,
+                Err(DatasourceError::QueryLimitExceeded) => {
+                    warn!(
+                        "{}/{}/{}/{} metatile layer {}: query_limit exceeded - skipping layer",
+                        tileset, zoom, xtile, ytile, layer.name
+                    );
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let elapsed = now.elapsed();
+            if let Some(ref mut stats) = stats {
+                stats.add(
+                    format!("tile_ms.{}.{}.{}", tileset, layer.name, zoom),
+                    elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64,
+                );
+                stats.add(
+                    format!("feature_count.{}.{}.{}", tileset, layer.name, zoom),
+                    num_features as u64,
+                );
+            }
+            debug!(
+                "{}/{}/{}/{} metatile layer {}: {} features",
+                tileset, zoom, xtile, ytile, layer.name, num_features
+            );
+            for ((_, _, tile), mvt_layer) in tiles.iter_mut().zip(mvt_layers) {
+                if mvt_layer.get_features().len() > 0 {
+                    tile.add_layer(mvt_layer);
+                }
+            }
+        }
+        Ok(tiles
+            .into_iter()
+            .map(|(x, y, tile)| (x, y, tile.mvt_tile))
+            .collect())
     }
     /// Fetch or create vector tile from input at x, y, z
+    ///
+    /// Returns `None` for empty tiles and tiles outside of the tileset zoom levels.
     pub fn tile_cached(
         &self,
         tileset: &str,
@@ -129,13 +366,9 @@ impl MvtService {
         zoom: u8,
         gzip: bool,
         stats: Option<&mut Statistics>,
-    ) -> Option<Vec<u8>> {
-        // Reverse y for XYZ scheme (TODO: protocol instead of CRS dependent?)
-        let y = if self.grid.srid == 3857 {
-            self.grid.ytile_from_xyz(ytile, zoom)
-        } else {
-            ytile
-        };
+    ) -> Result<Option<Vec<u8>>, DatasourceError> {
+        // Reverse y for XYZ scheme
+        let y = self.grid.ytile_from_scheme(ytile, zoom);
         let path = format!("{}/{}/{}/{}.pbf", tileset, zoom, xtile, ytile);
 
         let ts = self
@@ -143,7 +376,10 @@ impl MvtService {
             .expect(&format!("Tileset '{}' not found", tileset));
 
         if zoom < ts.minzoom() || zoom > ts.maxzoom() {
-            return None;
+            return Ok(None);
+        }
+        if zoom < self.grid.minzoom() || zoom > self.grid.maxzoom() {
+            return Ok(None);
         }
 
         let mut tile: Option<Vec<u8>> = None;
@@ -162,11 +398,11 @@ impl MvtService {
 
         // Return tile from cache
         if let Some(tilegz) = tile {
-            return Some(Tile::tile_content(tilegz, gzip));
+            return Ok(Some(Tile::tile_content(tilegz, gzip)));
         }
 
         // Request tile and write into cache
-        let mvt_tile = self.tile(tileset, xtile, y, zoom, stats);
+        let mvt_tile = self.tile(tileset, xtile, y, zoom, stats)?;
         // Spec: A Vector Tile SHOULD contain at least one layer.
         if mvt_tile.get_layers().len() > 0 {
             let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
@@ -180,18 +416,16 @@ impl MvtService {
                     ts.name, zoom
                 );
             }
-            Some(Tile::tile_content(tilegz, gzip))
+            Ok(Some(Tile::tile_content(tilegz, gzip)))
         } else {
             // We don't save empty tiles
             // When serving from file cache return 204 No Content
             // Nginx: try_files $uri = 204;
             debug!("{} - Skipping empty tile", path);
-            None
+            Ok(None)
         }
     }
-    fn progress_bar(&self, msg: &str, limits: &ExtentInt) -> ProgressBar<Stdout> {
-        let tiles =
-            (limits.maxx as u64 - limits.minx as u64) * (limits.maxy as u64 - limits.miny as u64);
+    fn progress_bar(&self, msg: &str, tiles: u64) -> ProgressBar<Stdout> {
         let mut pb = ProgressBar::new(tiles);
         pb.message(msg);
         //pb.set_max_refresh_rate(Some(Duration::from_millis(200)));
@@ -254,9 +488,13 @@ impl MvtService {
             debug!("tile limits: {:?}", ext_proj);
 
             let tolerance = 0;
-            let limits = self.grid.tile_limits(ext_proj, tolerance);
+            // Extents crossing the antimeridian are seeded in two parts
+            let span_limits = self.grid.tile_span_limits(&ext_proj, tolerance);
 
-            let ts_minzoom = cmp::max(tileset.minzoom(), minzoom.unwrap_or(0));
+            let ts_minzoom = *[tileset.minzoom(), minzoom.unwrap_or(0), self.grid.minzoom()]
+                .iter()
+                .max()
+                .unwrap_or(&0);
             let ts_maxzoom = *[
                 tileset.maxzoom(),
                 maxzoom.unwrap_or(99),
@@ -271,46 +509,84 @@ impl MvtService {
             if maxzoom.is_some() && maxzoom.unwrap() > ts_maxzoom {
                 warn!("Skipping zoom levels >{}", ts_maxzoom);
             }
-            let griditer = GridIterator::new(ts_minzoom, ts_maxzoom, limits.clone());
+            let meta = tileset.metatile();
+            let mut metatiles_done = HashSet::new();
+            let griditer = (ts_minzoom..=ts_maxzoom).flat_map(|zoom| {
+                span_limits
+                    .iter()
+                    .filter(move |limits| {
+                        let limit = &limits[zoom as usize];
+                        limit.maxx > limit.minx && limit.maxy > limit.miny
+                    })
+                    .flat_map(move |limits| GridIterator::new(zoom, zoom, limits.clone()))
+            });
             let mut pb = ProgressBar::new(0);
             let mut pb_z = !ts_minzoom;
             for (zoom, xtile, ytile) in griditer {
                 if progress && zoom != pb_z {
                     pb_z = zoom;
-                    let ref limit = limits[zoom as usize];
-                    debug!("level {}: {:?}", zoom, limit);
-                    pb = self.progress_bar(&format!("Level {}: ", zoom), &limit);
+                    let tiles = span_limits
+                        .iter()
+                        .map(|limits| {
+                            let limit = &limits[zoom as usize];
+                            debug!("level {}: {:?}", zoom, limit);
+                            u64::from(limit.maxx.saturating_sub(limit.minx))
+                                * u64::from(limit.maxy.saturating_sub(limit.miny))
+                        })
+                        .sum();
+                    pb = self.progress_bar(&format!("Level {}: ", zoom), tiles);
                     pb.tick();
                 }
 
+                if meta > 1 {
+                    let meta_idx = (zoom, xtile / meta as u32, ytile / meta as u32);
+                    if !metatiles_done.insert(meta_idx) {
+                        // Already generated with another tile of this metatile
+                        if progress {
+                            pb.inc();
+                        }
+                        continue;
+                    }
+                    let skip = tileno % nodes != nodeno;
+                    tileno += 1;
+                    if !skip {
+                        self.generate_metatile(
+                            tileset, xtile, ytile, zoom, meta, overwrite, &mut stats,
+                        );
+                    }
+                    if progress {
+                        pb.inc();
+                    }
+                    continue;
+                }
+
                 let skip = tileno % nodes != nodeno;
                 tileno += 1;
                 if skip {
                     continue;
                 }
 
-                // Store Mercator tiles in xyz scheme, others in TMS scheme.
-                let y = if self.grid.srid == 3857 {
-                    self.grid.ytile_from_xyz(ytile, zoom)
-                } else {
-                    ytile
-                };
+                // Store tiles in the adressing scheme of the grid
+                let y = self.grid.ytile_from_scheme(ytile, zoom);
                 let path = format!("{}/{}/{}/{}.pbf", &tileset.name, zoom, xtile, y);
 
                 if overwrite || !self.cache.exists(&path) {
                     // Entry doesn't exist, or we're ignoring it, so generate it
-                    let mvt_tile = self.tile(
+                    match self.tile(
                         &tileset.name,
                         xtile as u32,
                         ytile as u32,
                         zoom,
                         Some(&mut stats),
-                    );
-                    if mvt_tile.get_layers().len() > 0 {
-                        let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
-                        if let Err(ioerr) = self.cache.write(&path, &tilegz) {
-                            error!("Error writing {}: {}", path, ioerr);
+                    ) {
+                        Ok(mvt_tile) if mvt_tile.get_layers().len() > 0 => {
+                            let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
+                            if let Err(ioerr) = self.cache.write(&path, &tilegz) {
+                                error!("Error writing {}: {}", path, ioerr);
+                            }
                         }
+                        Ok(_) => {}
+                        Err(err) => error!("Error generating {}: {}", path, err),
                     }
                 }
 
@@ -324,6 +600,47 @@ impl MvtService {
         }
         stats
     }
+    /// Generate and store all tiles of the metatile containing x, y, z in TMS adressing scheme
+    fn generate_metatile(
+        &self,
+        tileset: &Tileset,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        meta: u8,
+        overwrite: bool,
+        stats: &mut Statistics,
+    ) {
+        // Store tiles in the adressing scheme of the grid
+        let path = |x: u32, y: u32| {
+            let y = self.grid.ytile_from_scheme(y, zoom);
+            format!("{}/{}/{}/{}.pbf", &tileset.name, zoom, x, y)
+        };
+        if !overwrite {
+            let limits = self.grid.metatile_limits(xtile, ytile, zoom, meta);
+            let all_cached = (limits.miny..limits.maxy)
+                .all(|y| (limits.minx..limits.maxx).all(|x| self.cache.exists(&path(x, y))));
+            if all_cached {
+                return;
+            }
+        }
+        let tiles = match self.metatile(&tileset.name, xtile, ytile, zoom, meta, Some(stats)) {
+            Ok(tiles) => tiles,
+            Err(err) => {
+                error!("Error generating metatile {}: {}", path(xtile, ytile), err);
+                return;
+            }
+        };
+        for (x, y, mvt_tile) in tiles {
+            let path = path(x, y);
+            if mvt_tile.get_layers().len() > 0 && (overwrite || !self.cache.exists(&path)) {
+                let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
+                if let Err(ioerr) = self.cache.write(&path, &tilegz) {
+                    error!("Error writing {}: {}", path, ioerr);
+                }
+            }
+        }
+    }
     pub fn init_cache(&self) {
         info!("{}", &self.cache.info());
         for tileset in &self.tilesets {
@@ -377,7 +694,10 @@ impl MvtService {
                 continue;
             }
 
-            let ts_minzoom = cmp::max(tileset.minzoom(), minzoom.unwrap_or(0));
+            let ts_minzoom = *[tileset.minzoom(), minzoom.unwrap_or(0), self.grid.minzoom()]
+                .iter()
+                .max()
+                .unwrap_or(&0);
             let ts_maxzoom = *[
                 tileset.maxzoom(),
                 maxzoom.unwrap_or(99),
@@ -408,13 +728,19 @@ impl MvtService {
                     debug!("level {}: {:?}", zoom, limit);
                     let xtile = limit.minx;
                     let ytile = limit.miny;
-                    let mvt_tile = self.tile(
+                    let mvt_tile = match self.tile(
                         &tileset.name,
                         xtile as u32,
                         ytile as u32,
                         zoom,
                         Some(&mut stats),
-                    );
+                    ) {
+                        Ok(mvt_tile) => mvt_tile,
+                        Err(err) => {
+                            error!("Error creating tile {}/{}/{}: {}", zoom, xtile, ytile, err);
+                            vector_tile::Tile::new()
+                        }
+                    };
                     stats.add(
                         format!("tile_bytes.{}.total.{}", &tileset.name, zoom),
                         Tile::size(&mvt_tile) as u64,
@@ -454,14 +780,33 @@ impl MvtService {
         }
 
         let mut cfg = lines.join("\n") + "\n";
-        cfg.push_str(&layer.gen_runtime_config());
-        if let &Datasource::Postgis(ref pg) = ds {
-            if layer.query(0).is_none() {
-                let zoom = 0; // relevant?
-                let query = pg.build_query_sql(layer, 3857, zoom, None, true).unwrap();
-                // Remove quotes from column names for better readability
-                cfg.push_str(&format!("#sql = \"\"\"{}\"\"\"\n", query.replace('"', "")))
+        match ds {
+            &Datasource::Postgis(ref pg)
+                if layer.query.iter().all(|q| q.sql.is_none()) && !pg.is_offline() =>
+            {
+                // Pin the query with detected columns and their casts
+                let sql = pg.build_query_sql(layer, grid_srid, 0, None, true);
+                let mut layer = layer.clone();
+                if layer.query.is_empty() {
+                    layer.query = vec![LayerQuery {
+                        minzoom: 0,
+                        maxzoom: None,
+                        simplify: None,
+                        tolerance: None,
+                        sql: sql,
+                        filter: None,
+                        group_by: None,
+                        aggregate: None,
+                    }];
+                } else {
+                    // Keep zoom level dependent filters, simplification and grouping
+                    for query in layer.query.iter_mut() {
+                        query.sql = sql.clone();
+                    }
+                }
+                cfg.push_str(&layer.gen_runtime_config());
             }
+            _ => cfg.push_str(&layer.gen_runtime_config()),
         }
         cfg
     }
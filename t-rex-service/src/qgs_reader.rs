@@ -163,6 +163,8 @@ pub fn read_qgs(fname: &str) -> (Datasources, Tileset) {
         start_zoom: None,
         layers: Vec::new(),
         cache_limits: None,
+        metatile: None,
+        max_features: None,
     };
     for qgslayer in projectlayers.find_all("maplayer") {
         let layertype = qgslayer.get_attr("type").expect("Missing attribute 'type'");
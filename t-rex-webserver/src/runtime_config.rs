@@ -29,9 +29,9 @@ fn set_layer_buffer_defaults(layer: &mut Layer, simplify: bool, clip: bool) {
         Some(ref geom) => {
             if clip {
                 if geom.contains("POLYGON") {
-                    Some(1)
+                    Some(1.0)
                 } else {
-                    Some(0)
+                    Some(0.0)
                 }
             } else {
                 None
@@ -71,7 +71,7 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
             println!("Error reading configuration - {} ", err);
             process::exit(1)
         });
-        svc.connect();
+        connect_service(&mut svc);
         svc
     } else {
         let cache = match args.value_of("cache") {
@@ -105,9 +105,12 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
                 bool::from_str(args.value_of("detect-geometry-types").unwrap_or("true"))
                     .unwrap_or(false);
             for (_name, ds) in &datasources.datasources {
-                let dsconn = ds.connected();
-                let mut layers = dsconn.detect_layers(detect_geometry_types);
-                while let Some(mut l) = layers.pop() {
+                let dsconn = ds.try_connected().unwrap_or_else(|err| {
+                    println!("Error connecting datasource - {}", err);
+                    process::exit(1)
+                });
+                let layers = dsconn.detect_layers(detect_geometry_types);
+                for mut l in layers {
                     l.no_transform = no_transform;
                     let extent = dsconn.layer_extent(&l, 3857);
                     set_layer_buffer_defaults(&mut l, simplify, clip);
@@ -121,6 +124,8 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
                         start_zoom: None,
                         layers: vec![l],
                         cache_limits: None,
+                        metatile: None,
+                        max_features: None,
                     };
                     tilesets.push(tileset);
                 }
@@ -133,11 +138,29 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
             tilesets: tilesets,
             cache: cache,
         };
-        svc.connect(); //TODO: ugly - we connect twice
+        connect_service(&mut svc); //TODO: ugly - we connect twice
         svc
     }
 }
 
+/// Connect datasources and exit if a database is not reachable
+fn connect_service(service: &mut MvtService) {
+    if let Err(err) = service.connect() {
+        println!("Error connecting datasource - {}", err);
+        process::exit(1)
+    }
+}
+
+/// Prepare datasource queries and exit on invalid queries
+pub fn prepare_service_queries(service: &mut MvtService) {
+    if let Err(errors) = service.prepare_feature_queries() {
+        for err in errors {
+            println!("Error preparing query - {}", err);
+        }
+        process::exit(1)
+    }
+}
+
 pub fn gen_config(args: &ArgMatches) -> String {
     let toml = r#"
 [webserver]
@@ -154,7 +177,8 @@ port = 6767
         || args.value_of("datasource").is_some()
         || args.value_of("qgs").is_some()
     {
-        let service = service_from_args(&config_from_args(args), args);
+        let mut service = service_from_args(&config_from_args(args), args);
+        prepare_service_queries(&mut service);
         config = service.gen_runtime_config();
     } else {
         config = MvtService::gen_config();
@@ -192,6 +216,9 @@ fn test_runtime_config() {
     let toml = gen_config(&args);
     println!("{}", toml);
     assert_eq!(Some("# t-rex configuration"), toml.lines().next());
+    // Pinned queries of detected layers
+    assert!(toml.contains("geometry_type = \"POINT\"\nsrid = 3857\n"));
+    assert!(toml.contains("[[tileset.layer.query]]\nsql = \"\"\"SELECT "));
 
     let config = parse_config(toml, "").unwrap();
     let _service = MvtService::from_config(&config).unwrap();
@@ -5,7 +5,7 @@
 
 use crate::core::config::ApplicationCfg;
 use crate::mvt_service::MvtService;
-use crate::runtime_config::{config_from_args, service_from_args};
+use crate::runtime_config::{config_from_args, prepare_service_queries, service_from_args};
 use crate::static_files::StaticFiles;
 use actix_cors::Cors;
 use actix_files as fs;
@@ -139,7 +139,7 @@ async fn tile_pbf(
     let tile = service.tile_cached(tileset, x, y, z, gzip, None);
     let cache_max_age = config.webserver.cache_control_max_age.unwrap_or(300);
 
-    let resp = if let Some(tile) = tile {
+    let resp = if let Ok(Some(tile)) = tile {
         HttpResponse::Ok()
             .content_type("application/x-protobuf")
             .if_true(gzip, |r| {
@@ -149,6 +149,9 @@ async fn tile_pbf(
             })
             .header(header::CACHE_CONTROL, format!("max-age={}", cache_max_age))
             .body(tile) // TODO: chunked response
+    } else if let Err(err) = tile {
+        error!("{}/{}/{}/{}: {}", tileset, z, x, y, err);
+        HttpResponse::InternalServerError().finish()
     } else {
         HttpResponse::NoContent().finish()
     };
@@ -216,7 +219,7 @@ pub async fn webserver(args: ArgMatches<'static>) -> std::io::Result<()> {
     let static_dirs = config.webserver.static_.clone();
 
     let mut service = service_from_args(&config, &args);
-    service.prepare_feature_queries();
+    prepare_service_queries(&mut service);
     service.init_cache();
 
     let server = HttpServer::new(move || {
@@ -5,6 +5,8 @@
 
 //!Tile grids
 
+use serde_json::json;
+use std::cmp;
 use std::f64::consts;
 
 /// Geographic extent
@@ -25,6 +27,9 @@ pub struct ExtentInt {
     pub maxy: u32,
 }
 
+/// Standardized rendering pixel size in meters (0.28mm) as defined by the OGC SLD specification
+const OGC_PIXEL_SIZE: f64 = 0.00028;
+
 // Max grid cell numbers
 type CellIndex = (u32, u32);
 
@@ -35,6 +40,15 @@ pub enum Origin {
     BottomLeft, //TopRight, BottomRight
 }
 
+/// Tile adressing scheme of requested tiles
+///
+/// `Tms` counts tile rows from the grid origin, `Xyz` in the opposite direction.
+#[derive(PartialEq, Clone, Debug)]
+pub enum TileScheme {
+    Tms,
+    Xyz,
+}
+
 /// Grid units
 #[derive(PartialEq, Clone, Debug)]
 pub enum Unit {
@@ -44,7 +58,7 @@ pub enum Unit {
 }
 
 /// Tile grid
-#[derive(Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct Grid {
     /// The width of an individual tile, in pixels.
     width: u16,
@@ -69,11 +83,50 @@ pub struct Grid {
     resolutions: Vec<f64>,
     /// maxx/maxy for each resolution
     level_max: Vec<CellIndex>,
+    /// Lowest served zoom level
+    zoom_min: u8,
+    /// Highest served zoom level (None: last resolution)
+    zoom_max: Option<u8>,
     /// Grid origin
     pub origin: Origin,
+    /// Adressing scheme of requested tiles
+    pub scheme: TileScheme,
+    /// Screen pixel size in meters used for scale denominators (default: 0.28mm)
+    pub pixel_size: f64,
 }
 
+/// Configuration name, EPSG code and constructor of a predefined grid
+type GridCtor = (&'static str, i32, fn() -> Grid);
+
+/// Predefined grids with configuration name and EPSG code
+const PREDEFINED_GRIDS: &[GridCtor] = &[
+    ("wgs84", 4326, Grid::wgs84),
+    ("web_mercator", 3857, Grid::web_mercator),
+    ("swissgrid_lv95", 2056, Grid::lv95),
+    ("bng", 27700, Grid::british_national_grid),
+];
+
 impl Grid {
+    /// Predefined grid by name (e.g. "web_mercator")
+    pub fn predefined(name: &str) -> Option<Grid> {
+        PREDEFINED_GRIDS
+            .iter()
+            .find(|(gridname, _, _)| *gridname == name)
+            .map(|(_, _, grid)| grid())
+    }
+
+    /// Predefined grid for an EPSG code (e.g. 3857)
+    pub fn from_epsg(epsg: i32) -> Result<Grid, String> {
+        PREDEFINED_GRIDS
+            .iter()
+            .find(|(_, code, _)| *code == epsg)
+            .map(|(_, _, grid)| grid())
+            .ok_or(format!(
+                "No predefined grid for EPSG:{} - use a custom grid definition instead",
+                epsg
+            ))
+    }
+
     /// WGS84 grid
     pub fn wgs84() -> Grid {
         Grid::new(
@@ -113,7 +166,7 @@ impl Grid {
 
     /// Web Mercator grid (Google maps compatible)
     pub fn web_mercator() -> Grid {
-        Grid::new(
+        let mut grid = Grid::new(
             256,
             256,
             Extent {
@@ -151,6 +204,78 @@ impl Grid {
                 0.037322767717371225,
             ],
             Origin::BottomLeft,
+        );
+        // Web Mercator tiles are served in XYZ scheme by convention
+        grid.scheme = TileScheme::Xyz;
+        grid
+    }
+
+    /// Web Mercator grid with 512x512 pixel tiles
+    pub fn web_mercator_512() -> Grid {
+        Grid::web_mercator().with_tile_size(512)
+    }
+
+    /// Grid with square tiles of a given size covering the same extent per tile
+    ///
+    /// Resolutions are scaled, so that a tile covers the same ground area as before.
+    pub fn with_tile_size(&self, tile_size: u16) -> Grid {
+        let factor = self.width as f64 / tile_size as f64;
+        let mut grid = Grid::new(
+            tile_size,
+            tile_size,
+            self.extent.clone(),
+            self.srid,
+            self.units.clone(),
+            self.resolutions.iter().map(|res| res * factor).collect(),
+            self.origin.clone(),
+        );
+        grid.scheme = self.scheme.clone();
+        grid.pixel_size = self.pixel_size;
+        grid.zoom_min = self.zoom_min;
+        grid.zoom_max = self.zoom_max;
+        grid
+    }
+
+    /// Swiss LV95 grid (EPSG:2056) as used by swisstopo
+    pub fn lv95() -> Grid {
+        Grid::new(
+            256,
+            256,
+            Extent {
+                minx: 2420000.0,
+                miny: 1030000.0,
+                maxx: 2900000.0,
+                maxy: 1350000.0,
+            },
+            2056,
+            Unit::Meters,
+            vec![
+                4000.0, 3750.0, 3500.0, 3250.0, 3000.0, 2750.0, 2500.0, 2250.0, 2000.0, 1750.0,
+                1500.0, 1250.0, 1000.0, 750.0, 650.0, 500.0, 250.0, 100.0, 50.0, 20.0, 10.0, 5.0,
+                2.5, 2.0, 1.5, 1.0, 0.5, 0.25, 0.1,
+            ],
+            Origin::TopLeft,
+        )
+    }
+
+    /// British National Grid (EPSG:27700) as used by the OS Maps API
+    pub fn british_national_grid() -> Grid {
+        Grid::new(
+            256,
+            256,
+            Extent {
+                minx: -238375.0,
+                miny: 0.0,
+                maxx: 900000.0,
+                maxy: 1376256.0,
+            },
+            27700,
+            Unit::Meters,
+            vec![
+                896.0, 448.0, 224.0, 112.0, 56.0, 28.0, 14.0, 7.0, 3.5, 1.75, 0.875, 0.4375,
+                0.21875, 0.109375,
+            ],
+            Origin::BottomLeft,
         )
     }
 
@@ -171,16 +296,74 @@ impl Grid {
             units,
             resolutions,
             origin,
+            scheme: TileScheme::Tms,
+            pixel_size: OGC_PIXEL_SIZE,
             level_max: Vec::new(),
+            zoom_min: 0,
+            zoom_max: None,
         };
         grid.level_max = grid.level_max();
         grid
     }
+    /// Grid description as TileJSON (https://github.com/mapbox/tilejson-spec)
+    ///
+    /// Bounds are omitted, if the grid extent can't be reprojected to WGS84.
+    pub fn to_tilejson(&self) -> serde_json::Value {
+        let mut tilejson = json!({
+            "tilejson": "2.2.0",
+            "scheme": match (&self.origin, &self.scheme) {
+                (Origin::BottomLeft, TileScheme::Tms) | (Origin::TopLeft, TileScheme::Xyz) => "tms",
+                (Origin::BottomLeft, TileScheme::Xyz) | (Origin::TopLeft, TileScheme::Tms) => "xyz",
+            },
+            "minzoom": self.minzoom(),
+            "maxzoom": self.maxzoom(),
+            "t-rex": {
+                "srid": self.srid,
+                "tile_size": [self.width, self.height],
+                "resolutions": self.resolutions,
+            }
+        });
+        if let Ok(bounds) = reproject_extent(&self.extent, self.srid, 4326) {
+            tilejson["bounds"] = json!([bounds.minx, bounds.miny, bounds.maxx, bounds.maxy]);
+        }
+        tilejson
+    }
     pub fn nlevels(&self) -> u8 {
         self.resolutions.len() as u8
     }
+    /// Lowest served zoom level
+    pub fn minzoom(&self) -> u8 {
+        self.zoom_min
+    }
+    /// Highest served zoom level
     pub fn maxzoom(&self) -> u8 {
-        self.nlevels() - 1
+        self.zoom_max.unwrap_or(self.nlevels() - 1)
+    }
+    /// Restrict served zoom levels without removing resolutions
+    pub fn set_zoom_range(&mut self, minzoom: u8, maxzoom: u8) -> Result<(), String> {
+        if minzoom > maxzoom || maxzoom >= self.nlevels() {
+            return Err(format!(
+                "Invalid grid zoom range {}-{} for {} resolutions",
+                minzoom,
+                maxzoom,
+                self.nlevels()
+            ));
+        }
+        self.zoom_min = minzoom;
+        self.zoom_max = Some(maxzoom);
+        Ok(())
+    }
+    /// Resolution of a zoom level in grid units per pixel
+    pub fn resolution(&self, zoom: u8) -> f64 {
+        self.resolutions[zoom as usize]
+    }
+    /// Resolutions of all zoom levels, ordered from largest to smallest
+    pub fn resolutions(&self) -> &[f64] {
+        &self.resolutions
+    }
+    /// Width and height of a tile in pixels
+    pub fn tile_size(&self) -> (u16, u16) {
+        (self.width, self.height)
     }
     pub fn pixel_width(&self, zoom: u8) -> f64 {
         const METERS_PER_DEGREE: f64 = 6378137.0 * 2.0 * consts::PI / 360.0;
@@ -191,11 +374,27 @@ impl Grid {
         }
     }
     pub fn scale_denominator(&self, zoom: u8) -> f64 {
-        const PIXEL_SCREEN_WIDTH: f64 = 0.00028;
         // https://github.com/mapnik/mapnik/wiki/ScaleAndPpi#scale-denominator
         // Mapnik calculates it's default at about 90.7 PPI, which originates from an assumed standard pixel size
         // of 0.28 millimeters as defined by the OGC (Open Geospatial Consortium) SLD (Styled Layer Descriptor) Specification.
-        self.pixel_width(zoom) / PIXEL_SCREEN_WIDTH
+        // High-DPI clients use smaller pixel sizes (e.g. 0.14mm).
+        self.pixel_width(zoom) / self.pixel_size
+    }
+    /// Pixel width in meters at the center of a tile extent
+    ///
+    /// For degree grids, the width of a pixel shrinks with the cosine of the latitude.
+    pub fn tile_pixel_width(&self, extent: &Extent, zoom: u8) -> f64 {
+        match self.units {
+            Unit::Degrees => {
+                let lat = ((extent.miny + extent.maxy) / 2.0).clamp(-90.0, 90.0);
+                self.pixel_width(zoom) * lat.to_radians().cos()
+            }
+            Unit::Meters | Unit::Feet => self.pixel_width(zoom),
+        }
+    }
+    /// Scale denominator at the center of a tile extent
+    pub fn tile_scale_denominator(&self, extent: &Extent, zoom: u8) -> f64 {
+        self.tile_pixel_width(extent, zoom) / self.pixel_size
     }
     /// Extent of a given tile in the grid given its x, y, and z in TMS adressing scheme
     pub fn tile_extent(&self, xtile: u32, ytile: u32, zoom: u8) -> Extent {
@@ -230,6 +429,184 @@ impl Grid {
         let y = self.ytile_from_xyz(ytile, zoom);
         self.tile_extent(xtile, y, zoom)
     }
+    /// Convert tile row between the adressing scheme of the grid and TMS adressing scheme
+    pub fn ytile_from_scheme(&self, ytile: u32, zoom: u8) -> u32 {
+        match self.scheme {
+            TileScheme::Tms => ytile,
+            TileScheme::Xyz => self.ytile_from_xyz(ytile, zoom),
+        }
+    }
+    /// Extent of a given tile in the adressing scheme of the grid
+    pub fn tile_extent_for_scheme(&self, xtile: u32, ytile: u32, zoom: u8) -> Extent {
+        self.tile_extent(xtile, self.ytile_from_scheme(ytile, zoom), zoom)
+    }
+    /// Tile index limits of the metatile block of size `meta` x `meta` containing a given tile
+    ///
+    /// The block is aligned to multiples of `meta` and clamped to the grid level limits.
+    pub fn metatile_limits(&self, xtile: u32, ytile: u32, zoom: u8, meta: u8) -> ExtentInt {
+        let meta = cmp::max(meta, 1) as u32;
+        let (level_maxx, level_maxy) = self.level_max[zoom as usize];
+        let minx = xtile / meta * meta;
+        let miny = ytile / meta * meta;
+        ExtentInt {
+            minx,
+            miny,
+            maxx: cmp::min(minx + meta, level_maxx),
+            maxy: cmp::min(miny + meta, level_maxy),
+        }
+    }
+    /// Extent of the metatile block of size `meta` x `meta` containing a given tile in TMS adressing scheme
+    pub fn metatile_extent(&self, xtile: u32, ytile: u32, zoom: u8, meta: u8) -> Extent {
+        let limits = self.metatile_limits(xtile, ytile, zoom, meta);
+        let first = self.tile_extent(limits.minx, limits.miny, zoom);
+        let last = self.tile_extent(limits.maxx - 1, limits.maxy - 1, zoom);
+        Extent {
+            minx: first.minx.min(last.minx),
+            miny: first.miny.min(last.miny),
+            maxx: first.maxx.max(last.maxx),
+            maxy: first.maxy.max(last.maxy),
+        }
+    }
+    /// Metatile extent expanded by `buffer` grid units on each side
+    pub fn metatile_extent_buffered(
+        &self,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        meta: u8,
+        buffer: f64,
+    ) -> Extent {
+        let extent = self.metatile_extent(xtile, ytile, zoom, meta);
+        Extent {
+            minx: extent.minx - buffer,
+            miny: extent.miny - buffer,
+            maxx: extent.maxx + buffer,
+            maxy: extent.maxy + buffer,
+        }
+    }
+    /// Tile containing a given point (in grid units) in TMS adressing scheme
+    ///
+    /// Points on a tile boundary belong to the tile with the higher index,
+    /// points outside the grid are clamped to the nearest tile.
+    pub fn point_tile(&self, x: f64, y: f64, zoom: u8) -> CellIndex {
+        let res = self.resolutions[zoom as usize];
+        let unitheight = self.height as f64 * res;
+        let unitwidth = self.width as f64 * res;
+        let (maxx, maxy) = self.level_max[zoom as usize];
+        let xtile = ((x - self.extent.minx) / unitwidth).floor();
+        let ytile = match self.origin {
+            Origin::BottomLeft => ((y - self.extent.miny) / unitheight).floor(),
+            Origin::TopLeft => ((self.extent.maxy - y) / unitheight).floor(),
+        };
+        (
+            xtile.max(0.0).min((maxx - 1) as f64) as u32,
+            ytile.max(0.0).min((maxy - 1) as f64) as u32,
+        )
+    }
+    /// Tile containing a given point (in grid units) in XYZ adressing scheme
+    pub fn point_tile_xyz(&self, x: f64, y: f64, zoom: u8) -> CellIndex {
+        let (xtile, ytile) = self.point_tile(x, y, zoom);
+        (xtile, self.ytile_from_xyz(ytile, zoom))
+    }
+    /// Parent tile of a given tile in TMS adressing scheme
+    pub fn tile_parent(&self, xtile: u32, ytile: u32, zoom: u8) -> Option<(u32, u32, u8)> {
+        if zoom == 0 || zoom >= self.nlevels() {
+            return None;
+        }
+        // Parent tile contains the center of the given tile
+        let ratio = self.resolutions[zoom as usize] / self.resolutions[zoom as usize - 1];
+        let (maxx, maxy) = self.level_max[zoom as usize - 1];
+        let x = (((xtile as f64 + 0.5) * ratio).floor() as u32).min(maxx - 1);
+        let y = (((ytile as f64 + 0.5) * ratio).floor() as u32).min(maxy - 1);
+        Some((x, y, zoom - 1))
+    }
+    /// Child tiles of a given tile in TMS adressing scheme
+    pub fn tile_children(&self, xtile: u32, ytile: u32, zoom: u8) -> Vec<(u32, u32, u8)> {
+        const EPSILON: f64 = 0.0000001;
+        if zoom + 1 >= self.nlevels() {
+            return Vec::new();
+        }
+        let ratio = self.resolutions[zoom as usize] / self.resolutions[zoom as usize + 1];
+        let (maxx, maxy) = self.level_max[zoom as usize + 1];
+        let minx = (xtile as f64 * ratio + EPSILON).floor() as u32;
+        let endx = (((xtile + 1) as f64 * ratio - EPSILON).ceil() as u32).min(maxx);
+        let miny = (ytile as f64 * ratio + EPSILON).floor() as u32;
+        let endy = (((ytile + 1) as f64 * ratio - EPSILON).ceil() as u32).min(maxy);
+        let mut children = Vec::new();
+        for y in miny..endy {
+            for x in minx..endx {
+                children.push((x, y, zoom + 1));
+            }
+        }
+        children
+    }
+    /// Other children of the parent of a given tile in TMS adressing scheme
+    pub fn tile_siblings(&self, xtile: u32, ytile: u32, zoom: u8) -> Vec<(u32, u32, u8)> {
+        match self.tile_parent(xtile, ytile, zoom) {
+            Some((x, y, z)) => self
+                .tile_children(x, y, z)
+                .into_iter()
+                .filter(|&tile| tile != (xtile, ytile, zoom))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+    /// Quadkey of a given tile in XYZ adressing scheme (Bing Maps tile system)
+    ///
+    /// Returns `None` for grids which are not quad trees and for zoom levels
+    /// beyond the grid or the 32 bit tile index range.
+    pub fn quadkey(&self, xtile: u32, ytile: u32, zoom: u8) -> Option<String> {
+        if !self.is_quad_grid() || zoom >= self.nlevels() || zoom > 32 {
+            return None;
+        }
+        let quadkey = (1..=zoom)
+            .rev()
+            .map(|i| {
+                let mask = 1 << (i - 1);
+                let mut digit = 0;
+                if xtile & mask != 0 {
+                    digit += 1;
+                }
+                if ytile & mask != 0 {
+                    digit += 2;
+                }
+                std::char::from_digit(digit, 4).unwrap()
+            })
+            .collect();
+        Some(quadkey)
+    }
+    /// Tile x, y, and z in XYZ adressing scheme of a given quadkey
+    pub fn from_quadkey(&self, quadkey: &str) -> Result<(u32, u32, u8), String> {
+        if !self.is_quad_grid() {
+            return Err("Quadkeys are only supported for quad tree grids".to_string());
+        }
+        if quadkey.len() >= self.nlevels() as usize {
+            return Err(format!("Quadkey '{}' exceeds maximal zoom level", quadkey));
+        }
+        let zoom = quadkey.len() as u8;
+        let (mut xtile, mut ytile) = (0, 0);
+        for (i, c) in quadkey.chars().enumerate() {
+            let mask = 1 << (zoom as usize - i - 1);
+            match c {
+                '0' => {}
+                '1' => xtile |= mask,
+                '2' => ytile |= mask,
+                '3' => {
+                    xtile |= mask;
+                    ytile |= mask;
+                }
+                _ => return Err(format!("Invalid quadkey digit '{}'", c)),
+            }
+        }
+        Ok((xtile, ytile, zoom))
+    }
+    /// Every grid level is a quad subdivision of the single tile at level 0
+    fn is_quad_grid(&self) -> bool {
+        self.level_max
+            .iter()
+            .enumerate()
+            .all(|(zoom, &(maxx, maxy))| maxx == 1 << zoom && maxy == 1 << zoom)
+    }
     /// (maxx, maxy) of grid level
     pub(crate) fn level_limit(&self, zoom: u8) -> CellIndex {
         let res = self.resolutions[zoom as usize];
@@ -248,8 +625,143 @@ impl Grid {
             .map(|zoom| self.level_limit(zoom))
             .collect()
     }
+    /// Split a geographic extent crossing the antimeridian into an eastern and a western part
+    ///
+    /// An extent crosses the antimeridian if minx > maxx or if it exceeds the
+    /// grid extent on one side only (e.g. 170 to 190 degrees).
+    /// Extents of grids with other units than degrees are returned unchanged.
+    pub fn split_antimeridian(&self, extent: &Extent) -> Vec<Extent> {
+        if self.units != Unit::Degrees {
+            return vec![extent.clone()];
+        }
+        let world_width = self.extent.maxx - self.extent.minx;
+        let (mut minx, mut maxx) = (extent.minx, extent.maxx);
+        if minx <= maxx && maxx - minx >= world_width {
+            return vec![extent.clone()];
+        }
+        // Normalize longitudes outside of the grid extent
+        if maxx > self.extent.maxx {
+            maxx -= world_width;
+        }
+        if minx < self.extent.minx {
+            minx += world_width;
+        }
+        if minx <= maxx {
+            return vec![Extent {
+                minx,
+                maxx,
+                ..extent.clone()
+            }];
+        }
+        vec![
+            Extent {
+                minx,
+                maxx: self.extent.maxx,
+                ..extent.clone()
+            },
+            Extent {
+                minx: self.extent.minx,
+                maxx,
+                ..extent.clone()
+            },
+        ]
+    }
     /// Tile index limits covering extent
+    ///
+    /// Extent edges on a tile boundary don't include the adjacent tile.
+    /// Extents crossing the antimeridian are not split, use `tile_span_limits` for them.
+    /// Limits of zoom levels outside of `minzoom` and `maxzoom` are empty.
     pub fn tile_limits(&self, extent: Extent, tolerance: i32) -> Vec<ExtentInt> {
+        let mut limits = self.span_limits(extent, tolerance);
+        for (zoom, limit) in limits.iter_mut().enumerate() {
+            if zoom < self.minzoom() as usize || zoom > self.maxzoom() as usize {
+                *limit = ExtentInt {
+                    minx: 0,
+                    miny: 0,
+                    maxx: 0,
+                    maxy: 0,
+                };
+            }
+        }
+        limits
+    }
+    /// Tile index limits covering extent for each part of the extent split at the antimeridian
+    ///
+    /// Extents crossing the antimeridian (see `split_antimeridian`) return limits for
+    /// the eastern and the western part, which don't share any tile.
+    pub fn tile_span_limits(&self, extent: &Extent, tolerance: i32) -> Vec<Vec<ExtentInt>> {
+        if !self.intersects(extent) {
+            return Vec::new();
+        }
+        let mut spans: Vec<_> = self
+            .split_antimeridian(extent)
+            .into_iter()
+            .map(|span| self.tile_limits(span, tolerance))
+            .collect();
+        if let [east, west] = &mut spans[..] {
+            // Merge parts overlapping on levels with few tiles or with tolerance
+            for (east, west) in east.iter_mut().zip(west.iter_mut()) {
+                if west.maxx > east.minx && west.maxx > west.minx {
+                    east.minx = cmp::min(east.minx, west.minx);
+                    east.miny = cmp::min(east.miny, west.miny);
+                    east.maxx = cmp::max(east.maxx, west.maxx);
+                    east.maxy = cmp::max(east.maxy, west.maxy);
+                    west.maxx = west.minx;
+                }
+            }
+        }
+        spans
+    }
+    /// Number of tiles covering extent for each zoom level
+    pub fn tile_count(&self, extent: &Extent) -> Vec<u64> {
+        let mut counts = vec![0; self.nlevels() as usize];
+        for limits in self.tile_span_limits(extent, 0) {
+            for (count, limit) in counts.iter_mut().zip(limits) {
+                // Tile limits are exclusive of maxx/maxy
+                *count += u64::from(limit.maxx.saturating_sub(limit.minx))
+                    * u64::from(limit.maxy.saturating_sub(limit.miny));
+            }
+        }
+        counts
+    }
+    /// Number of tiles covering extent at a given zoom level
+    pub fn tile_count_at(&self, extent: &Extent, zoom: u8) -> u64 {
+        self.tile_count(extent)[zoom as usize]
+    }
+    /// Tiles covering extent at a given zoom level as `(x, y, z)` in TMS adressing scheme
+    pub fn tiles_in_extent(
+        &self,
+        extent: &Extent,
+        zoom: u8,
+    ) -> impl Iterator<Item = (u32, u32, u8)> {
+        self.tile_span_limits(extent, 0)
+            .into_iter()
+            .flat_map(move |mut limits| {
+                let limit = limits.swap_remove(zoom as usize);
+                let (miny, maxy) = (limit.miny, limit.maxy);
+                (limit.minx..limit.maxx).flat_map(move |x| (miny..maxy).map(move |y| (x, y, zoom)))
+            })
+    }
+    /// Tiles covering extent at a given zoom level as `(x, y, z)` in XYZ adressing scheme
+    pub fn tiles_in_extent_xyz<'a>(
+        &'a self,
+        extent: &Extent,
+        zoom: u8,
+    ) -> impl Iterator<Item = (u32, u32, u8)> + 'a {
+        self.tiles_in_extent(extent, zoom)
+            .map(move |(x, y, z)| (x, self.ytile_from_xyz(y, z), z))
+    }
+    /// Check whether extent overlaps the grid extent
+    fn intersects(&self, extent: &Extent) -> bool {
+        self.split_antimeridian(extent).iter().any(|span| {
+            span.minx < self.extent.maxx
+                && span.maxx > self.extent.minx
+                && span.miny < self.extent.maxy
+                && span.maxy > self.extent.miny
+        })
+    }
+    /// Tile index limits covering extent without antimeridian handling
+    fn span_limits(&self, extent: Extent, tolerance: i32) -> Vec<ExtentInt> {
         // Based on mapcache_grid_compute_limits
         const EPSILON: f64 = 0.0000001;
         (0..self.nlevels())
@@ -259,28 +771,23 @@ impl Grid {
                 let unitwidth = self.width as f64 * res;
                 let (level_maxx, level_maxy) = self.level_max[i as usize];
 
-                let (mut minx, mut maxx, mut miny, mut maxy) = match self.origin {
+                // Fractional tile coordinates of extent
+                let tx_min = (extent.minx - self.extent.minx) / unitwidth;
+                let tx_max = (extent.maxx - self.extent.minx) / unitwidth;
+                let (ty_min, ty_max) = match self.origin {
                     Origin::BottomLeft => (
-                        (((extent.minx - self.extent.minx) / unitwidth + EPSILON).floor() as i32)
-                            - tolerance,
-                        (((extent.maxx - self.extent.minx) / unitwidth - EPSILON).ceil() as i32)
-                            + tolerance,
-                        (((extent.miny - self.extent.miny) / unitheight + EPSILON).floor() as i32)
-                            - tolerance,
-                        (((extent.maxy - self.extent.miny) / unitheight - EPSILON).ceil() as i32)
-                            + tolerance,
+                        (extent.miny - self.extent.miny) / unitheight,
+                        (extent.maxy - self.extent.miny) / unitheight,
                     ),
                     Origin::TopLeft => (
-                        (((extent.minx - self.extent.minx) / unitwidth + EPSILON).floor() as i32)
-                            - tolerance,
-                        (((extent.maxx - self.extent.minx) / unitwidth - EPSILON).ceil() as i32)
-                            + tolerance,
-                        (((self.extent.maxy - extent.maxy) / unitheight + EPSILON).floor() as i32)
-                            - tolerance,
-                        (((self.extent.maxy - extent.miny) / unitheight - EPSILON).ceil() as i32)
-                            + tolerance,
+                        (self.extent.maxy - extent.maxy) / unitheight,
+                        (self.extent.maxy - extent.miny) / unitheight,
                     ),
                 };
+                let mut minx = (tx_min + EPSILON).floor() as i32 - tolerance;
+                let mut maxx = (tx_max - EPSILON).ceil() as i32 + tolerance;
+                let mut miny = (ty_min + EPSILON).floor() as i32 - tolerance;
+                let mut maxy = (ty_max - EPSILON).ceil() as i32 + tolerance;
 
                 // to avoid requesting out-of-range tiles
                 if minx < 0 {
@@ -307,16 +814,29 @@ impl Grid {
     }
 }
 
+/// Maximal latitude of Web Mercator
+const MAX_LAT: f64 = 85.0511287798066;
+
 /// Returns the Spherical Mercator (x, y) in meters
+///
+/// Latitudes are clamped to the Web Mercator limit of ±85.0511°,
+/// since the poles would project to infinity.
 fn lonlat_to_merc(lon: f64, lat: f64) -> (f64, f64) {
     // from mod web_mercator in grid_test
-    //lng, lat = truncate_lnglat(lng, lat)
+    let lat = lat.clamp(-MAX_LAT, MAX_LAT);
     let x = 6378137.0 * lon.to_radians();
     let y = 6378137.0 * ((consts::PI * 0.25) + (0.5 * lat.to_radians())).tan().ln();
     (x, y)
 }
 
-/// Projected extent
+/// Returns the WGS84 (lon, lat) of a Spherical Mercator point
+pub fn merc_to_lonlat(x: f64, y: f64) -> (f64, f64) {
+    let lon = (x / 6378137.0).to_degrees();
+    let lat = (2.0 * (y / 6378137.0).exp().atan() - consts::PI * 0.5).to_degrees();
+    (lon, lat)
+}
+
+/// Projected extent with latitudes clamped to the Web Mercator limit
 pub fn extent_to_merc(extent: &Extent) -> Extent {
     let (minx, miny) = lonlat_to_merc(extent.minx, extent.miny);
     let (maxx, maxy) = lonlat_to_merc(extent.maxx, extent.maxy);
@@ -327,3 +847,51 @@ pub fn extent_to_merc(extent: &Extent) -> Extent {
         maxy,
     }
 }
+
+/// Geographic extent of a projected extent
+pub fn extent_from_merc(extent: &Extent) -> Extent {
+    let (minx, miny) = merc_to_lonlat(extent.minx, extent.miny);
+    let (maxx, maxy) = merc_to_lonlat(extent.maxx, extent.maxy);
+    Extent {
+        minx,
+        miny,
+        maxx,
+        maxy,
+    }
+}
+
+/// Reproject extent between SRIDs
+///
+/// Supported are EPSG:4326 and EPSG:3857 and identical SRIDs.
+/// The edges of the extent are densified between the four corners to reduce
+/// distortion of the resulting bounding box.
+pub fn reproject_extent(extent: &Extent, from: i32, to: i32) -> Result<Extent, String> {
+    const SEGMENTS: u32 = 20;
+    let transform: fn(f64, f64) -> (f64, f64) = match (from, to) {
+        _ if from == to => return Ok(extent.clone()),
+        (4326, 3857) => lonlat_to_merc,
+        (3857, 4326) => merc_to_lonlat,
+        _ => {
+            return Err(format!(
+                "Unsupported reprojection from EPSG:{} to EPSG:{}",
+                from, to
+            ))
+        }
+    };
+    let mut points = Vec::new();
+    for i in 0..=SEGMENTS {
+        let f = i as f64 / SEGMENTS as f64;
+        let x = extent.minx + f * (extent.maxx - extent.minx);
+        let y = extent.miny + f * (extent.maxy - extent.miny);
+        points.push(transform(x, extent.miny));
+        points.push(transform(x, extent.maxy));
+        points.push(transform(extent.minx, y));
+        points.push(transform(extent.maxx, y));
+    }
+    Ok(Extent {
+        minx: points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min),
+        miny: points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min),
+        maxx: points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max),
+        maxy: points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max),
+    })
+}
@@ -3,7 +3,11 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::grid::{extent_to_merc, Extent, ExtentInt, Grid};
+use crate::grid::{
+    extent_from_merc, extent_to_merc, merc_to_lonlat, reproject_extent, Extent, ExtentInt, Grid,
+    Origin, TileScheme, Unit,
+};
+use serde_json::json;
 
 #[test]
 fn test_bbox() {
@@ -112,6 +116,10 @@ fn test_grid_calculations() {
 
     assert_eq!(grid.pixel_width(10), 152.87405657035254);
     assert_eq!(grid.scale_denominator(10), 545978.7734655448);
+    let mut retina_grid = grid.clone();
+    retina_grid.pixel_size = 0.00014;
+    assert_eq!(retina_grid.pixel_width(10), 152.87405657035254);
+    assert_eq!(retina_grid.scale_denominator(10), 1091957.5469310896);
 
     assert_eq!(grid.level_limit(0), (1, 1));
     assert_eq!(grid.level_limit(10), (1024, 1024));
@@ -187,6 +195,377 @@ fn test_grid_calculations() {
     );
 }
 
+#[test]
+fn test_tile_limits_boundaries() {
+    let grid = Grid::web_mercator();
+    let world_width = grid.extent.maxx - grid.extent.minx;
+    for zoom in 14..=18 {
+        let ntiles = 1u32 << zoom;
+        for &(xtile, ytile) in &[
+            (0, 0),
+            (8580, 10645),
+            (ntiles / 2 - 1, ntiles / 2),
+            (ntiles - 2, ntiles - 3),
+        ] {
+            let xtile = xtile % (ntiles - 1);
+            let ytile = ytile % (ntiles - 1);
+            let tile = ExtentInt {
+                minx: xtile,
+                miny: ytile,
+                maxx: xtile + 1,
+                maxy: ytile + 1,
+            };
+            // Extent edges exactly on tile boundaries
+            let extent = grid.tile_extent(xtile, ytile, zoom);
+            assert_eq!(grid.tile_limits(extent, 0)[zoom as usize], tile);
+            // Boundaries calculated from the world width instead of the resolution
+            let tile_width = world_width / ntiles as f64;
+            let extent = Extent {
+                minx: grid.extent.minx + xtile as f64 * tile_width,
+                miny: grid.extent.miny + ytile as f64 * tile_width,
+                maxx: grid.extent.minx + (xtile + 1) as f64 * tile_width,
+                maxy: grid.extent.miny + (ytile + 1) as f64 * tile_width,
+            };
+            assert_eq!(grid.tile_limits(extent, 0)[zoom as usize], tile);
+        }
+    }
+
+    let grid = Grid::lv95();
+    for zoom in 14..=18 {
+        let extent = grid.tile_extent(1, 1, zoom);
+        assert_eq!(
+            grid.tile_limits(extent, 0)[zoom as usize],
+            ExtentInt {
+                minx: 1,
+                miny: 1,
+                maxx: 2,
+                maxy: 2,
+            }
+        );
+    }
+}
+
+const WGS84_WORLD: Extent = Extent {
+    minx: -180.0,
+    miny: -90.0,
+    maxx: 180.0,
+    maxy: 90.0,
+};
+
+#[test]
+fn test_antimeridian_tile_limits() {
+    let grid = Grid::wgs84();
+    let extent = Extent {
+        minx: 170.0,
+        miny: -10.0,
+        maxx: -170.0,
+        maxy: 10.0,
+    };
+    let spans = grid.split_antimeridian(&extent);
+    assert_eq!(spans.len(), 2);
+    assert_eq!(
+        grid.tile_limits(spans[0].clone(), 0)[3],
+        ExtentInt {
+            minx: 15,
+            miny: 3,
+            maxx: 16,
+            maxy: 5,
+        }
+    );
+    assert_eq!(
+        grid.tile_limits(spans[1].clone(), 0)[3],
+        ExtentInt {
+            minx: 0,
+            miny: 3,
+            maxx: 1,
+            maxy: 5,
+        }
+    );
+    // Separate spans on both edges of the grid
+    let limits: Vec<_> = grid
+        .tile_span_limits(&extent, 0)
+        .iter()
+        .map(|limits| limits[3].clone())
+        .collect();
+    assert_eq!(
+        limits,
+        vec![
+            ExtentInt {
+                minx: 15,
+                miny: 3,
+                maxx: 16,
+                maxy: 5,
+            },
+            ExtentInt {
+                minx: 0,
+                miny: 3,
+                maxx: 1,
+                maxy: 5,
+            }
+        ]
+    );
+    assert_eq!(grid.tile_count_at(&extent, 3), 4);
+    assert_eq!(grid.tiles_in_extent(&extent, 3).count(), 4);
+    // Longitudes beyond 180 degrees
+    let extent = Extent {
+        minx: 170.0,
+        miny: -10.0,
+        maxx: 190.0,
+        maxy: 10.0,
+    };
+    assert_eq!(grid.split_antimeridian(&extent).len(), 2);
+    assert_eq!(grid.tile_count_at(&extent, 3), 4);
+    assert_eq!(grid.split_antimeridian(&WGS84_WORLD).len(), 1);
+
+    // No wraparound for projected grids
+    let grid = Grid::web_mercator();
+    let extent = Extent {
+        minx: 1000.0,
+        miny: 0.0,
+        maxx: -1000.0,
+        maxy: 10.0,
+    };
+    assert_eq!(grid.split_antimeridian(&extent), vec![extent]);
+}
+
+#[test]
+fn test_antimeridian_tile_count() {
+    let grid = Grid::wgs84();
+    let columns = |extent: &Extent, zoom: u8| {
+        let mut columns: Vec<_> = grid.tiles_in_extent(extent, zoom).map(|t| t.0).collect();
+        columns.dedup();
+        columns
+    };
+    // 10 degrees on each side of the antimeridian
+    let extent = Extent {
+        minx: 170.0,
+        miny: -10.0,
+        maxx: -170.0,
+        maxy: 10.0,
+    };
+    let (level_maxx, _) = grid.level_limit(8);
+    assert_eq!(level_maxx, 512);
+    let mut expected: Vec<u32> = (497..512).collect();
+    expected.extend(0..15);
+    assert_eq!(columns(&extent, 8), expected);
+    assert_eq!(
+        grid.tile_count_at(&extent, 8),
+        grid.tiles_in_extent(&extent, 8).count() as u64
+    );
+    // Tiny overshoot of the antimeridian adds only the first western column
+    let extent = Extent {
+        minx: 170.0,
+        miny: -10.0,
+        maxx: 180.0000001,
+        maxy: 10.0,
+    };
+    let mut expected: Vec<u32> = (497..512).collect();
+    expected.push(0);
+    assert_eq!(columns(&extent, 8), expected);
+    assert_eq!(
+        grid.tile_count_at(&extent, 8),
+        grid.tiles_in_extent(&extent, 8).count() as u64
+    );
+    // Single tile level covering both parts is not counted twice
+    let grid = Grid::new(
+        256,
+        256,
+        WGS84_WORLD,
+        4326,
+        Unit::Degrees,
+        vec![1.40625, 0.703125],
+        Origin::BottomLeft,
+    );
+    assert_eq!(grid.level_limit(0), (1, 1));
+    assert_eq!(grid.tiles_in_extent(&extent, 0).count(), 1);
+}
+
+#[test]
+fn test_predefined_grids() {
+    assert_eq!(Grid::predefined("web_mercator"), Some(Grid::web_mercator()));
+    assert_eq!(Grid::predefined("bng"), Some(Grid::british_national_grid()));
+    assert_eq!(Grid::predefined("mercator"), None);
+    assert_eq!(Grid::from_epsg(3857), Ok(Grid::web_mercator()));
+    assert_eq!(Grid::from_epsg(4326), Ok(Grid::wgs84()));
+    assert_eq!(Grid::from_epsg(2056), Ok(Grid::lv95()));
+    assert_eq!(
+        Grid::from_epsg(3035),
+        Err("No predefined grid for EPSG:3035 - use a custom grid definition instead".to_string())
+    );
+}
+
+#[test]
+fn test_grid_accessors() {
+    let grid = Grid::lv95();
+    assert_eq!(grid.tile_size(), (256, 256));
+    assert_eq!(grid.resolutions().len(), 29);
+    assert_eq!(grid.resolution(0), 4000.0);
+    assert_eq!(grid.resolution(28), 0.1);
+    let grid = Grid::web_mercator();
+    assert_eq!(grid.resolution(10), grid.pixel_width(10));
+}
+
+#[test]
+fn test_tile_count() {
+    let grid = Grid::web_mercator();
+    let counts = grid.tile_count(&grid.extent);
+    assert_eq!(counts.len(), 23);
+    assert_eq!(&counts[0..4], &[1, 4, 16, 64]);
+    assert_eq!(counts[22], 1 << 44);
+    assert_eq!(grid.tile_count_at(&grid.extent, 10), 1 << 20);
+
+    // Zurich
+    let extent = Extent {
+        minx: 940000.0,
+        miny: 5990000.0,
+        maxx: 970000.0,
+        maxy: 6010000.0,
+    };
+    assert_eq!(grid.tile_count_at(&extent, 0), 1);
+    assert_eq!(grid.tile_count_at(&extent, 12), 12);
+
+    // Outside of grid
+    let extent = Extent {
+        minx: -30000000.0,
+        miny: 0.0,
+        maxx: -25000000.0,
+        maxy: 1000000.0,
+    };
+    assert_eq!(grid.tile_count(&extent), vec![0; 23]);
+}
+
+#[test]
+fn test_tiles_in_extent() {
+    let grid = Grid::web_mercator();
+    let tiles: Vec<_> = grid.tiles_in_extent(&grid.extent, 1).collect();
+    assert_eq!(tiles, vec![(0, 0, 1), (0, 1, 1), (1, 0, 1), (1, 1, 1)]);
+
+    // Zurich
+    let extent = Extent {
+        minx: 940000.0,
+        miny: 5990000.0,
+        maxx: 970000.0,
+        maxy: 6010000.0,
+    };
+    for zoom in 0..15 {
+        assert_eq!(
+            grid.tiles_in_extent(&extent, zoom).count() as u64,
+            grid.tile_count_at(&extent, zoom)
+        );
+    }
+    let tiles: Vec<_> = grid.tiles_in_extent(&extent, 10).collect();
+    assert_eq!(tiles, vec![(536, 665, 10)]);
+    let tiles: Vec<_> = grid.tiles_in_extent_xyz(&extent, 10).collect();
+    assert_eq!(tiles, vec![(536, 358, 10)]);
+
+    // Outside of grid
+    let extent = Extent {
+        minx: -30000000.0,
+        miny: 0.0,
+        maxx: -25000000.0,
+        maxy: 1000000.0,
+    };
+    assert_eq!(grid.tiles_in_extent(&extent, 5).count(), 0);
+}
+
+#[test]
+fn test_tile_scale_denominator() {
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(3, 6, 3);
+    assert_eq!(grid.tile_pixel_width(&extent, 3), grid.pixel_width(3));
+    assert_eq!(
+        grid.tile_scale_denominator(&extent, 3),
+        grid.scale_denominator(3)
+    );
+
+    let grid = Grid::wgs84();
+    // Tile at the equator
+    let extent = Extent {
+        minx: 0.0,
+        miny: -1.0,
+        maxx: 2.0,
+        maxy: 1.0,
+    };
+    assert_eq!(grid.tile_pixel_width(&extent, 5), grid.pixel_width(5));
+    // Tile at 60° north
+    let extent = Extent {
+        minx: 0.0,
+        miny: 59.0,
+        maxx: 2.0,
+        maxy: 61.0,
+    };
+    let ratio = grid.tile_scale_denominator(&extent, 5) / grid.scale_denominator(5);
+    assert!((ratio - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_tile_scheme() {
+    let grid = Grid::web_mercator();
+    assert_eq!(grid.scheme, TileScheme::Xyz);
+    assert_eq!(grid.ytile_from_scheme(0, 3), 7);
+    assert_eq!(
+        grid.tile_extent_for_scheme(4, 2, 3),
+        grid.tile_extent_xyz(4, 2, 3)
+    );
+
+    let mut grid = Grid::lv95();
+    assert_eq!(grid.scheme, TileScheme::Tms);
+    assert_eq!(grid.ytile_from_scheme(0, 3), 0);
+    assert_eq!(
+        grid.tile_extent_for_scheme(4, 2, 3),
+        grid.tile_extent(4, 2, 3)
+    );
+    grid.scheme = TileScheme::Xyz;
+    assert_eq!(
+        grid.tile_extent_for_scheme(4, 2, 3),
+        grid.tile_extent_xyz(4, 2, 3)
+    );
+    assert_eq!(grid.to_tilejson()["scheme"], "tms");
+}
+
+#[test]
+fn test_zoom_range() {
+    let mut grid = Grid::web_mercator();
+    assert_eq!((grid.minzoom(), grid.maxzoom()), (0, 22));
+    grid.set_zoom_range(2, 14).unwrap();
+    assert_eq!((grid.minzoom(), grid.maxzoom()), (2, 14));
+    assert_eq!(grid.nlevels(), 23);
+    // Resolutions of higher levels are still available
+    assert_eq!(grid.pixel_width(18), 0.5971642834779396);
+    let limits = grid.tile_limits(grid.extent.clone(), 0);
+    assert_eq!(limits.len(), 23);
+    assert_eq!(limits[1].maxx - limits[1].minx, 0);
+    assert_eq!(limits[2].maxx - limits[2].minx, 4);
+    assert_eq!(limits[15].maxx - limits[15].minx, 0);
+    assert_eq!(grid.tile_count_at(&grid.extent, 14), 1 << 28);
+    assert_eq!(grid.tile_count_at(&grid.extent, 15), 0);
+    assert_eq!(grid.tiles_in_extent(&grid.extent, 1).count(), 0);
+    assert_eq!(grid.to_tilejson()["minzoom"], 2);
+    assert_eq!(grid.to_tilejson()["maxzoom"], 14);
+
+    assert_eq!(
+        grid.set_zoom_range(0, 23),
+        Err("Invalid grid zoom range 0-23 for 23 resolutions".to_string())
+    );
+    assert!(grid.set_zoom_range(5, 4).is_err());
+}
+
+#[test]
+fn test_tile_size() {
+    let grid = Grid::web_mercator();
+    let grid512 = Grid::web_mercator_512();
+    assert_eq!(grid512.tile_size(), (512, 512));
+    assert_eq!(grid512.nlevels(), grid.nlevels());
+    assert_eq!(grid512.scheme, grid.scheme);
+    assert_eq!(grid512.tile_extent(0, 0, 0), grid.tile_extent(0, 0, 0));
+    assert_eq!(grid512.tile_extent(3, 5, 4), grid.tile_extent(3, 5, 4));
+    assert_eq!(grid512.pixel_width(10), grid.pixel_width(10) / 2.0);
+    assert_eq!(
+        grid512.tile_count_at(&grid512.extent, 5),
+        grid.tile_count_at(&grid.extent, 5)
+    );
+}
+
 #[test]
 fn test_wgs84_grid() {
     let grid = Grid::wgs84();
@@ -195,6 +574,290 @@ fn test_wgs84_grid() {
     assert_eq!(grid.scale_denominator(10), 272989.38673277234);
 }
 
+#[test]
+fn test_point_tile() {
+    let grid = Grid::web_mercator();
+    assert_eq!(grid.point_tile(0.0, 0.0, 0), (0, 0));
+    // Tile boundaries belong to the upper tile
+    assert_eq!(grid.point_tile(0.0, 0.0, 1), (1, 1));
+    assert_eq!(grid.point_tile(-1.0, -1.0, 1), (0, 0));
+    let extent = grid.tile_extent(33, 41, 6);
+    assert_eq!(grid.point_tile(extent.minx, extent.miny, 6), (33, 41));
+    assert_eq!(grid.point_tile_xyz(extent.minx, extent.miny, 6), (33, 22));
+    // Max edge and outside points are clamped
+    assert_eq!(
+        grid.point_tile(grid.extent.maxx, grid.extent.maxy, 10),
+        (1023, 1023)
+    );
+    assert_eq!(grid.point_tile(-3e7, 3e7, 2), (0, 3));
+
+    let grid = Grid::wgs84();
+    assert_eq!(grid.point_tile(7.44, 46.95, 0), (1, 0));
+    assert_eq!(grid.point_tile(7.44, 46.95, 6), (66, 48));
+    assert_eq!(grid.point_tile(180.0, 90.0, 1), (3, 1));
+
+    let grid = Grid::new(
+        256,
+        256,
+        Extent {
+            minx: 2420000.0,
+            miny: 1030000.0,
+            maxx: 2900000.0,
+            maxy: 1350000.0,
+        },
+        2056,
+        Unit::Meters,
+        vec![1000.0, 500.0, 250.0],
+        Origin::TopLeft,
+    );
+    assert_eq!(grid.point_tile(2420000.0, 1350000.0, 2), (0, 0));
+    assert_eq!(grid.point_tile(2600000.0, 1200000.0, 2), (2, 2));
+    assert_eq!(grid.point_tile(2900000.0, 1030000.0, 2), (7, 4));
+}
+
+#[test]
+fn test_metatile_extent() {
+    let grid = Grid::web_mercator();
+    assert_eq!(
+        grid.metatile_limits(5, 6, 3, 4),
+        ExtentInt {
+            minx: 4,
+            miny: 4,
+            maxx: 8,
+            maxy: 8,
+        }
+    );
+    let first = grid.tile_extent(4, 4, 3);
+    let last = grid.tile_extent(7, 7, 3);
+    assert_eq!(
+        grid.metatile_extent(5, 6, 3, 4),
+        Extent {
+            minx: first.minx,
+            miny: first.miny,
+            maxx: last.maxx,
+            maxy: last.maxy,
+        }
+    );
+    // Single tile
+    assert_eq!(
+        grid.metatile_extent(33, 41, 6, 1),
+        grid.tile_extent(33, 41, 6)
+    );
+    // Block clamped to level limits
+    assert_eq!(grid.metatile_extent(1, 1, 1, 4), grid.tile_extent(0, 0, 0));
+
+    let grid = Grid::new(
+        256,
+        256,
+        Extent {
+            minx: 2420000.0,
+            miny: 1030000.0,
+            maxx: 2900000.0,
+            maxy: 1350000.0,
+        },
+        2056,
+        Unit::Meters,
+        vec![1000.0, 500.0, 250.0],
+        Origin::TopLeft,
+    );
+    assert_eq!(
+        grid.metatile_limits(7, 4, 2, 4),
+        ExtentInt {
+            minx: 4,
+            miny: 4,
+            maxx: 8,
+            maxy: 5,
+        }
+    );
+    assert_eq!(
+        grid.metatile_extent(1, 1, 1, 4),
+        Extent {
+            minx: 2420000.0,
+            miny: 966000.0,
+            maxx: 2932000.0,
+            maxy: 1350000.0,
+        }
+    );
+    assert_eq!(
+        grid.metatile_extent_buffered(1, 1, 1, 4, 5000.0),
+        Extent {
+            minx: 2415000.0,
+            miny: 961000.0,
+            maxx: 2937000.0,
+            maxy: 1355000.0,
+        }
+    );
+}
+
+#[test]
+fn test_tile_navigation() {
+    let grid = Grid::web_mercator();
+    assert_eq!(grid.tile_parent(0, 0, 0), None);
+    assert_eq!(grid.tile_parent(33, 41, 6), Some((16, 20, 5)));
+
+    let children = grid.tile_children(0, 0, 0);
+    assert_eq!(children, [(0, 0, 1), (1, 0, 1), (0, 1, 1), (1, 1, 1)]);
+    let extents: Vec<Extent> = children
+        .iter()
+        .map(|&(x, y, z)| grid.tile_extent(x, y, z))
+        .collect();
+    assert_eq!(
+        Extent {
+            minx: extents.iter().map(|e| e.minx).fold(f64::MAX, f64::min),
+            miny: extents.iter().map(|e| e.miny).fold(f64::MAX, f64::min),
+            maxx: extents.iter().map(|e| e.maxx).fold(f64::MIN, f64::max),
+            maxy: extents.iter().map(|e| e.maxy).fold(f64::MIN, f64::max),
+        },
+        grid.tile_extent(0, 0, 0)
+    );
+    for &(x, y, z) in &children {
+        assert_eq!(grid.tile_parent(x, y, z), Some((0, 0, 0)));
+    }
+    assert_eq!(
+        grid.tile_siblings(1, 0, 1),
+        [(0, 0, 1), (0, 1, 1), (1, 1, 1)]
+    );
+    assert_eq!(grid.tile_siblings(0, 0, 0), []);
+    assert_eq!(grid.tile_children(0, 0, grid.maxzoom()), []);
+
+    // Grid with top left origin and partially covered levels
+    let grid = Grid::new(
+        256,
+        256,
+        Extent {
+            minx: 2420000.0,
+            miny: 1030000.0,
+            maxx: 2900000.0,
+            maxy: 1350000.0,
+        },
+        2056,
+        Unit::Meters,
+        vec![1000.0, 500.0, 250.0],
+        Origin::TopLeft,
+    );
+    assert_eq!(
+        grid.tile_children(0, 0, 0),
+        [(0, 0, 1), (1, 0, 1), (0, 1, 1), (1, 1, 1)]
+    );
+    // Level 1 has 3 rows
+    assert_eq!(grid.tile_children(1, 1, 0), [(2, 2, 1), (3, 2, 1)]);
+    assert_eq!(grid.tile_parent(3, 2, 1), Some((1, 1, 0)));
+    assert_eq!(grid.tile_siblings(3, 2, 1), [(2, 2, 1)]);
+}
+
+#[test]
+fn test_quadkey() {
+    let grid = Grid::web_mercator();
+    assert_eq!(grid.quadkey(0, 0, 0), Some("".to_string()));
+    assert_eq!(grid.quadkey(3, 5, 3), Some("213".to_string()));
+    assert_eq!(grid.from_quadkey("213"), Ok((3, 5, 3)));
+    for zoom in 0..=20 {
+        let max = (1u32 << zoom) - 1;
+        for &(x, y) in &[(0, 0), (max, 0), (0, max), (max, max), (max / 3, max / 2)] {
+            let quadkey = grid.quadkey(x, y, zoom).unwrap();
+            assert_eq!(quadkey.len(), zoom as usize);
+            assert_eq!(grid.from_quadkey(&quadkey), Ok((x, y, zoom)));
+        }
+    }
+
+    assert_eq!(
+        grid.from_quadkey("214"),
+        Err("Invalid quadkey digit '4'".to_string())
+    );
+    assert!(grid.from_quadkey(&"0".repeat(23)).is_err());
+    assert_eq!(grid.quadkey(0, 0, 23), None);
+    assert_eq!(grid.quadkey(0, 0, 40), None);
+    assert_eq!(Grid::wgs84().quadkey(0, 0, 1), None);
+    assert_eq!(
+        Grid::wgs84().from_quadkey("0"),
+        Err("Quadkeys are only supported for quad tree grids".to_string())
+    );
+}
+
+#[test]
+fn test_lv95_grid() {
+    let grid = Grid::lv95();
+
+    assert_eq!(grid.nlevels(), 29);
+    assert_eq!(
+        grid.tile_extent(0, 0, 0),
+        Extent {
+            minx: 2420000.0,
+            miny: 326000.0,
+            maxx: 3444000.0,
+            maxy: 1350000.0,
+        }
+    );
+    assert_eq!(grid.level_limit(0), (1, 1));
+    assert_eq!(grid.level_limit(20), (188, 125));
+}
+
+#[test]
+fn test_british_national_grid() {
+    let grid = Grid::british_national_grid();
+
+    assert_eq!(grid.nlevels(), 14);
+    assert_eq!(grid.maxzoom(), 13);
+    // Published OS Maps API scales
+    let scales = [
+        3200000.0, 1600000.0, 800000.0, 400000.0, 200000.0, 100000.0, 50000.0, 25000.0, 12500.0,
+        6250.0, 3125.0, 1562.5, 781.25, 390.625,
+    ];
+    for (zoom, scale) in scales.iter().enumerate() {
+        assert!((grid.scale_denominator(zoom as u8) - scale).abs() < 1e-6);
+    }
+    assert_eq!(
+        grid.tile_extent(0, 0, 0),
+        Extent {
+            minx: -238375.0,
+            miny: 0.0,
+            maxx: -8999.0,
+            maxy: 229376.0,
+        }
+    );
+    assert_eq!(grid.level_limit(0), (5, 6));
+}
+
+#[test]
+fn test_tilejson() {
+    let grid = Grid::web_mercator();
+    let tilejson = grid.to_tilejson();
+    assert_eq!(tilejson["scheme"], "xyz");
+    assert_eq!(tilejson["minzoom"], 0);
+    assert_eq!(tilejson["maxzoom"], 22);
+    assert_eq!(tilejson["t-rex"]["srid"], 3857);
+    assert_eq!(tilejson["t-rex"]["resolutions"][10], 152.87405657035254);
+    let bounds: Vec<f64> = tilejson["bounds"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_f64().unwrap())
+        .collect();
+    assert!((bounds[3] - 85.0511287798066).abs() < 1e-9);
+    let extent = reproject_extent(
+        &Extent {
+            minx: bounds[0],
+            miny: bounds[1],
+            maxx: bounds[2],
+            maxy: bounds[3],
+        },
+        4326,
+        3857,
+    )
+    .unwrap();
+    assert!((extent.minx - grid.extent.minx).abs() < 1e-6);
+    assert!((extent.miny - grid.extent.miny).abs() < 1e-6);
+    assert!((extent.maxx - grid.extent.maxx).abs() < 1e-6);
+    assert!((extent.maxy - grid.extent.maxy).abs() < 1e-6);
+
+    let tilejson = Grid::wgs84().to_tilejson();
+    assert_eq!(tilejson["scheme"], "tms");
+    assert_eq!(tilejson["bounds"], json!([-180.0, -90.0, 180.0, 90.0]));
+    let tilejson = Grid::lv95().to_tilejson();
+    assert_eq!(tilejson["scheme"], "xyz");
+    assert!(tilejson.get("bounds").is_none());
+}
+
 #[test]
 fn test_projected_extent() {
     let extent_wgs84 = Extent {
@@ -218,6 +881,76 @@ fn test_projected_extent() {
         maxy: 6982997.920389788,
     };
     assert_eq!(extent_to_merc(&extent_wgs84), extent_3857);
+
+    // Latitudes beyond the Web Mercator limit are clamped
+    let world = extent_to_merc(&Grid::wgs84().extent);
+    let merc = Grid::web_mercator().extent;
+    assert!(world.minx.is_finite() && world.miny.is_finite());
+    assert!(world.maxx.is_finite() && world.maxy.is_finite());
+    assert!((world.minx - merc.minx).abs() < 1e-3);
+    assert!((world.miny - merc.miny).abs() < 1e-3);
+    assert!((world.maxx - merc.maxx).abs() < 1e-3);
+    assert!((world.maxy - merc.maxy).abs() < 1e-3);
+}
+
+#[test]
+fn test_extent_from_merc() {
+    let (lon, lat) = merc_to_lonlat(0.0, 0.0);
+    assert!(lon.abs() < 1e-12 && lat.abs() < 1e-12);
+    let (lon, lat) = merc_to_lonlat(20037508.342789248, 20037508.342789248);
+    assert!((lon - 180.0).abs() < 1e-9);
+    assert!((lat - 85.0511287798066).abs() < 1e-9);
+
+    for extent_wgs84 in &[
+        Extent {
+            minx: 4.0,
+            miny: 52.0,
+            maxx: 5.0,
+            maxy: 53.0,
+        },
+        Extent {
+            minx: -122.5,
+            miny: -33.9,
+            maxx: 151.2,
+            maxy: 64.1,
+        },
+    ] {
+        let extent = extent_from_merc(&extent_to_merc(extent_wgs84));
+        assert!((extent.minx - extent_wgs84.minx).abs() < 1e-9);
+        assert!((extent.miny - extent_wgs84.miny).abs() < 1e-9);
+        assert!((extent.maxx - extent_wgs84.maxx).abs() < 1e-9);
+        assert!((extent.maxy - extent_wgs84.maxy).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_reproject_extent() {
+    let extent_wgs84 = Extent {
+        minx: 4.0,
+        miny: 52.0,
+        maxx: 5.0,
+        maxy: 53.0,
+    };
+    let extent_3857 = reproject_extent(&extent_wgs84, 4326, 3857).unwrap();
+    assert_eq!(extent_3857, extent_to_merc(&extent_wgs84));
+    let extent = reproject_extent(&extent_3857, 3857, 4326).unwrap();
+    assert!((extent.minx - 4.0).abs() < 1e-9);
+    assert!((extent.miny - 52.0).abs() < 1e-9);
+    assert!((extent.maxx - 5.0).abs() < 1e-9);
+    assert!((extent.maxy - 53.0).abs() < 1e-9);
+
+    // Latitudes are clamped to the Web Mercator extent
+    let world = reproject_extent(&Grid::wgs84().extent, 4326, 3857).unwrap();
+    assert!((world.maxy - Grid::web_mercator().extent.maxy).abs() < 1e-3);
+
+    assert_eq!(
+        reproject_extent(&extent_wgs84, 2056, 2056),
+        Ok(extent_wgs84.clone())
+    );
+    assert_eq!(
+        reproject_extent(&extent_wgs84, 4326, 2056),
+        Err("Unsupported reprojection from EPSG:4326 to EPSG:2056".to_string())
+    );
 }
 
 mod web_mercator {
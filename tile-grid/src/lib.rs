@@ -77,5 +77,8 @@ mod grid_iterator;
 #[cfg(test)]
 mod grid_test;
 
-pub use grid::{extent_to_merc, Extent, ExtentInt, Grid, Origin, Unit};
+pub use grid::{
+    extent_from_merc, extent_to_merc, merc_to_lonlat, reproject_extent, Extent, ExtentInt, Grid,
+    Origin, TileScheme, Unit,
+};
 pub use grid_iterator::GridIterator;